@@ -76,6 +76,9 @@ pub fn update(msg: Msg, model: &mut Model, ctx: &mut Context, orders: &mut impl
                     services: Vec::new(),
                     tags: Vec::new(),
                     chain: 1,
+                    native: String::new(),
+                    wallet: None,
+                    kind: shared::AddressKind::Unknown,
                 });
             } else {
                 model.new_address = None;
@@ -35,6 +35,7 @@ pub fn update(msg: Msg, model: &mut Model, ctx: &mut Context, orders: &mut impl
                 model.new_service = Some(shared::Service {
                     title: String::new(),
                     id: None,
+                    ..Default::default()
                 });
             } else {
                 model.new_service = None;
@@ -34,10 +34,7 @@ pub fn update(msg: Msg, model: &mut Model, ctx: &mut Context, orders: &mut impl
         }
         Msg::TagNew => {
             if model.new_tag.is_none() {
-                model.new_tag = Some(shared::Tag {
-                    title: String::new(),
-                    id: None,
-                });
+                model.new_tag = Some(shared::Tag::default());
             } else {
                 model.new_tag = None;
             }
@@ -1,12 +1,38 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20221201_160944_create_tables;
+mod m20230210_120000_create_block_table;
+mod m20230305_120000_add_block_timestamp;
+mod m20230320_120000_create_transfer_table;
+mod m20230402_120000_create_job_table;
+mod m20230410_120000_create_import_table;
+mod m20230418_120000_add_transfer_internal;
+mod m20230425_120000_create_wallet_table;
+mod m20230502_120000_add_address_kind;
+mod m20230509_120000_add_chain_disabled;
+mod m20230516_120000_create_service_hash_table;
+mod m20230523_120000_add_tag_parent;
+mod m20230530_120000_add_job_not_before;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20221201_160944_create_tables::Migration)]
+        vec![
+            Box::new(m20221201_160944_create_tables::Migration),
+            Box::new(m20230210_120000_create_block_table::Migration),
+            Box::new(m20230305_120000_add_block_timestamp::Migration),
+            Box::new(m20230320_120000_create_transfer_table::Migration),
+            Box::new(m20230402_120000_create_job_table::Migration),
+            Box::new(m20230410_120000_create_import_table::Migration),
+            Box::new(m20230418_120000_add_transfer_internal::Migration),
+            Box::new(m20230425_120000_create_wallet_table::Migration),
+            Box::new(m20230502_120000_add_address_kind::Migration),
+            Box::new(m20230509_120000_add_chain_disabled::Migration),
+            Box::new(m20230516_120000_create_service_hash_table::Migration),
+            Box::new(m20230523_120000_add_tag_parent::Migration),
+            Box::new(m20230530_120000_add_job_not_before::Migration),
+        ]
     }
 }
@@ -0,0 +1,190 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Block - ledger of processed blocks
+        manager
+            .create_table(
+                Table::create()
+                    .table(Block::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Block::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Block::Chain).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-chain-id")
+                            .from(Block::Table, Block::Chain)
+                            .to(Chain::Table, Chain::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(Block::Height).big_integer().not_null())
+                    .col(ColumnDef::new(Block::Slot).big_integer().null())
+                    .col(ColumnDef::new(Block::Hash).binary().not_null())
+                    .to_owned(),
+            )
+            .await?;
+        {
+            // Indexes
+            manager
+                .create_index(
+                    Index::create()
+                        .name("block-idx-height")
+                        .table(Block::Table)
+                        .col(Block::Chain)
+                        .col(Block::Height)
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .create_index(
+                    Index::create()
+                        .name("block-idx-slot")
+                        .table(Block::Table)
+                        .col(Block::Chain)
+                        .col(Block::Slot)
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .create_index(
+                    Index::create()
+                        .name("block-unique")
+                        .table(Block::Table)
+                        .col(Block::Chain)
+                        .col(Block::Hash)
+                        .unique()
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        // Provenance - removing block removes everything it brought
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Transaction::Table)
+                    .add_column(ColumnDef::new(Transaction::BlockId).big_integer().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk-transaction-block-id")
+                    .from(Transaction::Table, Transaction::BlockId)
+                    .to(Block::Table, Block::Id)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("transaction-idx-block")
+                    .table(Transaction::Table)
+                    .col(Transaction::BlockId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Address::Table)
+                    .add_column(ColumnDef::new(Address::BlockId).big_integer().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk-address-block-id")
+                    .from(Address::Table, Address::BlockId)
+                    .to(Block::Table, Block::Id)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("address-idx-block")
+                    .table(Address::Table)
+                    .col(Address::BlockId)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Transaction::Table)
+                    .drop_column(Transaction::BlockId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Address::Table)
+                    .drop_column(Address::BlockId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(Block::Table)
+                    .if_exists()
+                    .cascade()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Chain {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Block {
+    Table,
+    Id,
+    Chain,
+    Height,
+    Slot,
+    Hash,
+}
+
+#[derive(Iden)]
+enum Transaction {
+    Table,
+    BlockId,
+}
+
+#[derive(Iden)]
+enum Address {
+    Table,
+    BlockId,
+}
@@ -0,0 +1,220 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Asset - native coin, fungible or non fungible token
+        manager
+            .create_table(
+                Table::create()
+                    .table(Asset::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Asset::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Asset::Chain).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-chain-id")
+                            .from(Asset::Table, Asset::Chain)
+                            .to(Chain::Table, Chain::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(Asset::Kind).integer().not_null())
+                    // Token contract or Cardano policy id, empty for native coin
+                    .col(ColumnDef::new(Asset::Contract).binary().not_null())
+                    // Cardano asset name or NFT token id, empty otherwise
+                    .col(ColumnDef::new(Asset::Name).binary().not_null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("asset-unique")
+                    .table(Asset::Table)
+                    .col(Asset::Chain)
+                    .col(Asset::Contract)
+                    .col(Asset::Name)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // Transfer - value of one transaction input or output
+        manager
+            .create_table(
+                Table::create()
+                    .table(Transfer::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Transfer::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Transfer::TransactionId)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-transfer-transaction-id")
+                            .from(Transfer::Table, Transfer::TransactionId)
+                            .to(Transaction::Table, Transaction::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(Transfer::AddressId).big_integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-transfer-address-id")
+                            .from(Transfer::Table, Transfer::AddressId)
+                            .to(Address::Table, Address::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(Transfer::AssetId).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-transfer-asset-id")
+                            .from(Transfer::Table, Transfer::AssetId)
+                            .to(Asset::Table, Asset::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(Transfer::Output).boolean().not_null())
+                    .col(ColumnDef::new(Transfer::Index).integer().not_null())
+                    // Arbitrary precision, token amounts don't fit into any integer
+                    .col(ColumnDef::new(Transfer::Amount).decimal().not_null())
+                    .to_owned(),
+            )
+            .await?;
+        {
+            // Indexes
+            manager
+                .create_index(
+                    Index::create()
+                        .name("transfer-unique")
+                        .table(Transfer::Table)
+                        .col(Transfer::TransactionId)
+                        .col(Transfer::AssetId)
+                        .col(Transfer::Output)
+                        .col(Transfer::Index)
+                        .unique()
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .create_index(
+                    Index::create()
+                        .name("transfer-idx-address")
+                        .table(Transfer::Table)
+                        .col(Transfer::AddressId)
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .create_index(
+                    Index::create()
+                        .name("transfer-idx-asset")
+                        .table(Transfer::Table)
+                        .col(Transfer::AssetId)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        // Amount was stored truncated to 32 bits, transfers hold the real values
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Transaction::Table)
+                    .drop_column(Transaction::Amount)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Transaction::Table)
+                    .add_column(ColumnDef::new(Transaction::Amount).big_unsigned().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(Transfer::Table)
+                    .if_exists()
+                    .cascade()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(Asset::Table)
+                    .if_exists()
+                    .cascade()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Chain {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Address {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Transaction {
+    Table,
+    Id,
+    Amount,
+}
+
+#[derive(Iden)]
+enum Asset {
+    Table,
+    Id,
+    Chain,
+    Kind,
+    Contract,
+    Name,
+}
+
+#[derive(Iden)]
+enum Transfer {
+    Table,
+    Id,
+    TransactionId,
+    AddressId,
+    AssetId,
+    Output,
+    Index,
+    Amount,
+}
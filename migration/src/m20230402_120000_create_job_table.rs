@@ -0,0 +1,131 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Job - address crawl request consumed by the chain feed
+        manager
+            .create_table(
+                Table::create()
+                    .table(Job::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Job::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Job::Chain).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-job-chain-id")
+                            .from(Job::Table, Job::Chain)
+                            .to(Chain::Table, Chain::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(Job::AddressId).big_integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-job-address-id")
+                            .from(Job::Table, Job::AddressId)
+                            .to(Address::Table, Address::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    // Queued, running, done, failed or cancelled
+                    .col(ColumnDef::new(Job::State).integer().not_null())
+                    .col(
+                        ColumnDef::new(Job::Attempts)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(ColumnDef::new(Job::Error).text().null())
+                    .col(
+                        ColumnDef::new(Job::Created)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .extra("DEFAULT now()".to_string()),
+                    )
+                    .col(
+                        ColumnDef::new(Job::Started)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(Job::Finished)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        {
+            // Indexes
+            manager
+                .create_index(
+                    Index::create()
+                        .name("job-idx-chain-state")
+                        .table(Job::Table)
+                        .col(Job::Chain)
+                        .col(Job::State)
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .create_index(
+                    Index::create()
+                        .name("job-idx-address")
+                        .table(Job::Table)
+                        .col(Job::AddressId)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(Job::Table)
+                    .if_exists()
+                    .cascade()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Chain {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Address {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Job {
+    Table,
+    Id,
+    Chain,
+    AddressId,
+    State,
+    Attempts,
+    Error,
+    Created,
+    Started,
+    Finished,
+}
@@ -0,0 +1,90 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Import - progress of an import from an external source
+        manager
+            .create_table(
+                Table::create()
+                    .table(Import::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Import::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Import::Chain).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-import-chain-id")
+                            .from(Import::Table, Import::Chain)
+                            .to(Chain::Table, Chain::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(Import::Source).string().not_null())
+                    // Last imported record ID of the source
+                    .col(
+                        ColumnDef::new(Import::Position)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(Import::Updated)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("import-unique")
+                    .table(Import::Table)
+                    .col(Import::Chain)
+                    .col(Import::Source)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(Import::Table)
+                    .if_exists()
+                    .cascade()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Chain {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Import {
+    Table,
+    Id,
+    Chain,
+    Source,
+    Position,
+    Updated,
+}
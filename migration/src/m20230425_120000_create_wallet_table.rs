@@ -0,0 +1,126 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Wallet - addresses sharing one stake credential
+        manager
+            .create_table(
+                Table::create()
+                    .table(Wallet::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Wallet::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Wallet::Chain).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-wallet-chain-id")
+                            .from(Wallet::Table, Wallet::Chain)
+                            .to(Chain::Table, Chain::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(Wallet::Stake).binary().not_null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("wallet-unique")
+                    .table(Wallet::Table)
+                    .col(Wallet::Chain)
+                    .col(Wallet::Stake)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // Credentials of Shelley addresses, other addresses have none
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Address::Table)
+                    .add_column(ColumnDef::new(Address::Payment).binary().null())
+                    .add_column(ColumnDef::new(Address::Delegation).binary().null())
+                    .add_column(ColumnDef::new(Address::WalletId).big_integer().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk-address-wallet-id")
+                    .from(Address::Table, Address::WalletId)
+                    .to(Wallet::Table, Wallet::Id)
+                    .on_delete(ForeignKeyAction::SetNull)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("address-idx-wallet")
+                    .table(Address::Table)
+                    .col(Address::WalletId)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Address::Table)
+                    .drop_column(Address::Payment)
+                    .drop_column(Address::Delegation)
+                    .drop_column(Address::WalletId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(Wallet::Table)
+                    .if_exists()
+                    .cascade()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Chain {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Address {
+    Table,
+    Payment,
+    Delegation,
+    WalletId,
+}
+
+#[derive(Iden)]
+enum Wallet {
+    Table,
+    Id,
+    Chain,
+    Stake,
+}
@@ -0,0 +1,56 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Key, script or contract owner, unknown until a feed tells
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Address::Table)
+                    .add_column(
+                        ColumnDef::new(Address::Kind)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("address-idx-kind")
+                    .table(Address::Table)
+                    .col(Address::Chain)
+                    .col(Address::Kind)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Address::Table)
+                    .drop_column(Address::Kind)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Address {
+    Table,
+    Chain,
+    Kind,
+}
@@ -0,0 +1,133 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Service details from the registry files
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Service::Table)
+                    .add_column(
+                        ColumnDef::new(Service::Description)
+                            .text()
+                            .not_null()
+                            .default(""),
+                    )
+                    .add_column(ColumnDef::new(Service::Url).string().null())
+                    .add_column(
+                        ColumnDef::new(Service::Tags)
+                            .array(ColumnType::Integer(None))
+                            .not_null()
+                            .default(SimpleExpr::Custom(String::from("Array[]::integer[]"))),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Service hash - script hash, pool or address marking service addresses
+        manager
+            .create_table(
+                Table::create()
+                    .table(ServiceHash::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ServiceHash::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ServiceHash::Service).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-service_hash-service-id")
+                            .from(ServiceHash::Table, ServiceHash::Service)
+                            .to(Service::Table, Service::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(ServiceHash::Chain).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-service_hash-chain-id")
+                            .from(ServiceHash::Table, ServiceHash::Chain)
+                            .to(Chain::Table, Chain::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(ServiceHash::Role).integer().not_null())
+                    .col(ColumnDef::new(ServiceHash::Hash).binary().not_null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("service_hash-unique")
+                    .table(ServiceHash::Table)
+                    .col(ServiceHash::Service)
+                    .col(ServiceHash::Chain)
+                    .col(ServiceHash::Role)
+                    .col(ServiceHash::Hash)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("service_hash-idx-chain")
+                    .table(ServiceHash::Table)
+                    .col(ServiceHash::Chain)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ServiceHash::Table).to_owned())
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Service::Table)
+                    .drop_column(Service::Description)
+                    .drop_column(Service::Url)
+                    .drop_column(Service::Tags)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Service {
+    Table,
+    Id,
+    Description,
+    Url,
+    Tags,
+}
+
+#[derive(Iden)]
+enum ServiceHash {
+    Table,
+    Id,
+    Service,
+    Chain,
+    Role,
+    Hash,
+}
+
+#[derive(Iden)]
+enum Chain {
+    Table,
+    Id,
+}
@@ -0,0 +1,158 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, Statement};
+
+/// Rows `(id, title, parent, category)` of the tags in the `Tag` enum
+const SEED: &str = r#"
+    (1, 'Finance', NULL::integer, 1),
+    (301, 'Dex', 1, 1),
+    (302, 'Exchange', 1, 1),
+    (303, 'Atm', 1, 1),
+    (100, 'Game', NULL, 2),
+    (101, 'Eshop', NULL, 2),
+    (102, 'Gamble', NULL, 2),
+    (103, 'Bet', NULL, 2),
+    (104, 'Travel', NULL, 2),
+    (105, 'Sport', NULL, 2),
+    (106, 'Entertainment', NULL, 2),
+    (107, 'Trade', NULL, 2),
+    (201, 'Drugs', NULL, 3),
+    (202, 'Food', NULL, 3),
+    (203, 'Information', NULL, 3),
+    (204, 'Stream', NULL, 3),
+    (205, 'Podcast', NULL, 3),
+    (206, 'Video', NULL, 3),
+    (207, 'Audio', NULL, 3),
+    (208, 'Image', NULL, 3),
+    (1001, 'Pool', 301, 4),
+    (1002, 'Address', 301, 4),
+    (1003, 'Worker', 301, 4),
+    (1004, 'Order', 301, 4)
+"#;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Tags created under the IDs of the `Tag` enum with another title are
+        // not re-parented. Checked before any schema change as the migration
+        // doesn't run in a transaction.
+        let conflicts = manager
+            .get_connection()
+            .query_all(Statement::from_string(
+                manager.get_database_backend(),
+                format!(
+                    r#"
+                    SELECT T.id, T.title, S.title AS expected
+                    FROM tag T JOIN (VALUES {}) AS S(id, title, parent, category) ON S.id = T.id
+                    WHERE T.title <> S.title
+                    "#,
+                    SEED
+                ),
+            ))
+            .await?
+            .iter()
+            .map(|row| {
+                Ok(format!(
+                    "{} '{}' (expected '{}')",
+                    row.try_get::<i32>("", "id")?,
+                    row.try_get::<String>("", "title")?,
+                    row.try_get::<String>("", "expected")?
+                ))
+            })
+            .collect::<Result<Vec<String>, DbErr>>()?;
+        if !conflicts.is_empty() {
+            return Err(DbErr::Custom(format!(
+                "Tag IDs reserved by the tag tree are taken: {}",
+                conflicts.join(", ")
+            )));
+        }
+
+        // Tag tree - parent tag and the category of the tag group
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Tag::Table)
+                    .add_column(ColumnDef::new(Tag::Parent).integer().null())
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("fk-tag-parent-id")
+                            .from_tbl(Tag::Table)
+                            .from_col(Tag::Parent)
+                            .to_tbl(Tag::Table)
+                            .to_col(Tag::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .add_column(
+                        ColumnDef::new(Tag::Category)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("tag-idx-parent")
+                    .table(Tag::Table)
+                    .col(Tag::Parent)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Tags of the `Tag` enum, their numbering gives the groups
+        manager
+            .get_connection()
+            .execute(Statement::from_string(
+                manager.get_database_backend(),
+                format!(
+                    r#"
+                    INSERT INTO tag (id, title, parent, category)
+                    VALUES {}
+                    ON CONFLICT (id) DO UPDATE
+                        SET parent = EXCLUDED.parent, category = EXCLUDED.category
+                        WHERE tag.title = EXCLUDED.title;
+                    "#,
+                    SEED
+                ),
+            ))
+            .await?;
+        manager
+            .get_connection()
+            .execute(Statement::from_string(
+                manager.get_database_backend(),
+                String::from(
+                    "SELECT setval(pg_get_serial_sequence('tag', 'id'), (SELECT MAX(id) FROM tag));",
+                ),
+            ))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Tag::Table)
+                    .drop_foreign_key(Alias::new("fk-tag-parent-id"))
+                    .drop_column(Tag::Parent)
+                    .drop_column(Tag::Category)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Tag {
+    Table,
+    Id,
+    Parent,
+    Category,
+}
@@ -8,7 +8,12 @@ use serde::{Deserialize, Serialize};
 pub struct AddressRelationHuman {
     pub id: i64,
     pub hex: String,
+    #[serde(default)]
+    pub native: String,
     pub human: String,
+    /// Wallet whose relations are aggregated in the wallet mode
+    #[serde(default)]
+    pub wallet: Option<i64>,
     pub inputs: Vec<AddressRefHuman>,
     pub outputs: Vec<AddressRefHuman>,
     pub mixed_in: Vec<AddressRefHuman>,
@@ -22,7 +27,12 @@ pub struct AddressRelationHuman {
 pub struct AddressRelation {
     pub id: i64,
     pub hex: String,
+    #[serde(default)]
+    pub native: String,
     pub human: String,
+    /// Wallet whose relations are aggregated in the wallet mode
+    #[serde(default)]
+    pub wallet: Option<i64>,
     pub inputs: Vec<AddressRef>,
     pub outputs: Vec<AddressRef>,
     pub mixed_in: Vec<AddressRef>,
@@ -38,8 +48,17 @@ pub struct Address {
     pub hash: String,
     pub title: Option<String>,
     pub chain: i32,
+    /// Address in the native form of the chain, bech32 or Byron base58 for
+    /// Cardano and EIP-55 checksummed hex for EVM chains
+    #[serde(default)]
+    pub native: String,
     pub services: Vec<i32>,
     pub tags: Vec<i32>,
+    /// Wallet of the addresses sharing the stake credential
+    #[serde(default)]
+    pub wallet: Option<i64>,
+    #[serde(default)]
+    pub kind: AddressKind,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -47,8 +66,20 @@ pub struct Address {
 pub struct AddressRef {
     pub id: i64,
     pub hex: String,
+    #[serde(default)]
+    pub native: String,
     pub human: String,
     pub quantity: i32,
+    #[serde(default)]
+    pub kind: AddressKind,
+    /// Wallet of the counterparty, its addresses are merged in the wallet mode
+    #[serde(default)]
+    pub wallet: Option<i64>,
+    /// Edges of the quantity made by internal contract calls
+    #[serde(default)]
+    pub internal: i32,
+    /// Value moved by the counterparty, known only for inputs and outputs
+    pub amounts: Vec<super::Amount>,
     pub tags: Vec<i32>,
     pub services: Vec<i32>,
 }
@@ -58,8 +89,81 @@ pub struct AddressRef {
 pub struct AddressRefHuman {
     pub id: i64,
     pub hex: String,
+    #[serde(default)]
+    pub native: String,
     pub human: String,
     pub quantity: i32,
+    #[serde(default)]
+    pub kind: AddressKind,
+    /// Wallet of the counterparty, its addresses are merged in the wallet mode
+    #[serde(default)]
+    pub wallet: Option<i64>,
+    /// Edges of the quantity made by internal contract calls
+    #[serde(default)]
+    pub internal: i32,
+    /// Value moved by the counterparty, known only for inputs and outputs
+    pub amounts: Vec<super::Amount>,
     pub tags: Vec<String>,
     pub services: Vec<String>,
 }
+
+/// Owner of the address, as far as the chain tells it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub enum AddressKind {
+    #[default]
+    Unknown = 0,
+    /// Controlled by a private key
+    Key = 1,
+    /// Cardano or Bitcoin script
+    Script = 2,
+    /// EVM smart contract
+    Contract = 3,
+}
+
+impl AddressKind {
+    /// Kind stored in database
+    pub fn from_i32(kind: i32) -> Self {
+        match kind {
+            1 => AddressKind::Key,
+            2 => AddressKind::Script,
+            3 => AddressKind::Contract,
+            _ => AddressKind::Unknown,
+        }
+    }
+}
+
+/// Filter of the address lists
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct AddressQuery {
+    pub kind: Option<AddressKind>,
+    /// Tag filter matches the descendant tags too
+    pub descendants: Option<bool>,
+}
+
+/// Addresses sharing one stake credential
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct Wallet {
+    pub id: i64,
+    pub chain: i32,
+    /// Stake key or script hash
+    pub stake: String,
+    pub addresses: Vec<Address>,
+}
+
+/// Relations of the address alone or of the whole wallet of the address
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub enum RelationMode {
+    #[default]
+    Address,
+    Wallet,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct RelationQuery {
+    pub mode: Option<RelationMode>,
+}
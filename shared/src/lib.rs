@@ -9,7 +9,10 @@ use strum_macros::EnumIter;
 
 mod address;
 
-pub use address::{Address, AddressRef, AddressRefHuman, AddressRelation, AddressRelationHuman};
+pub use address::{
+    Address, AddressKind, AddressQuery, AddressRef, AddressRefHuman, AddressRelation,
+    AddressRelationHuman, RelationMode, RelationQuery, Wallet,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[cfg_attr(feature = "schema", derive(Schema))]
@@ -17,6 +20,9 @@ pub struct Chain {
     pub id: Option<i32>,
     pub title: String,
     pub params: ChainParam,
+    /// Disabled chain keeps its data but its feed doesn't run
+    #[serde(default)]
+    pub disabled: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -24,6 +30,13 @@ pub struct Chain {
 pub struct Service {
     pub id: Option<i32>,
     pub title: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Tags given to the marked addresses of the service
+    #[serde(default)]
+    pub tags: Vec<i32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -31,17 +44,135 @@ pub struct Service {
 pub struct Tag {
     pub id: Option<i32>,
     pub title: String,
+    #[serde(default)]
+    pub parent: Option<i32>,
+    #[serde(default)]
+    pub category: TagCategory,
+}
+
+/// Group of the tag, follows the numbering of the built-in tags
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub enum TagCategory {
+    #[default]
+    Other = 0,
+    /// Finance 1 and its children 3xx
+    Finance = 1,
+    /// Activities 1xx
+    Activity = 2,
+    /// Subjects 2xx
+    Subject = 3,
+    /// Roles of DEX addresses 100x
+    Dex = 4,
+}
+
+impl TagCategory {
+    /// Category stored in database
+    pub fn from_i32(category: i32) -> Self {
+        match category {
+            1 => TagCategory::Finance,
+            2 => TagCategory::Activity,
+            3 => TagCategory::Subject,
+            4 => TagCategory::Dex,
+            _ => TagCategory::Other,
+        }
+    }
+}
+
+/// Tag with its child tags
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct TagNode {
+    pub tag: Tag,
+    pub children: Vec<TagNode>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[cfg_attr(feature = "schema", derive(Schema))]
 pub struct Transaction {
-    pub id: Option<i32>,
+    pub id: Option<i64>,
     pub chain: i32,
     pub hash: Vec<u8>,
-    pub amount: i64,
     pub from: Vec<i64>,
     pub to: Vec<i64>,
+    pub block: Option<i64>,
+    /// Unix timestamp in seconds
+    pub timestamp: Option<i64>,
+    /// Position of the transaction in the block
+    pub position: Option<i32>,
+    pub transfers: Vec<Transfer>,
+}
+
+/// Value moved by one transaction input or output
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct Transfer {
+    pub address: i64,
+    pub asset: Asset,
+    pub output: bool,
+    pub index: i32,
+    /// Decimal number in the smallest unit of the asset
+    pub amount: String,
+    /// Moved by a contract call inside the transaction
+    #[serde(default)]
+    pub internal: bool,
+}
+
+/// Sum of moved value of one asset
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct Amount {
+    pub asset: Asset,
+    /// Decimal number in the smallest unit of the asset
+    pub amount: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct Asset {
+    pub id: i32,
+    pub chain: i32,
+    pub kind: AssetKind,
+    /// Token contract or Cardano policy id in hex, empty for native coin
+    pub contract: String,
+    /// Cardano asset name or NFT token id in hex
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub enum AssetKind {
+    #[default]
+    Native = 0,
+    Erc20 = 1,
+    Erc721 = 2,
+    Erc1155 = 3,
+    CardanoToken = 4,
+}
+
+impl AssetKind {
+    /// Kind stored in database
+    pub fn from_i32(kind: i32) -> Self {
+        match kind {
+            1 => AssetKind::Erc20,
+            2 => AssetKind::Erc721,
+            3 => AssetKind::Erc1155,
+            4 => AssetKind::CardanoToken,
+            _ => AssetKind::Native,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct Block {
+    pub id: i64,
+    pub chain: i32,
+    pub height: i64,
+    pub slot: Option<i64>,
+    pub hash: String,
+    /// Unix timestamp in seconds
+    pub timestamp: Option<i64>,
 }
 
 /// Only for internal look up
@@ -50,8 +181,11 @@ pub struct PrivAddress {
     pub title: String,
     pub chain: i32,
     pub hash: Vec<u8>,
+    pub native: String,
     pub tags: Vec<i32>,
     pub services: Vec<i32>,
+    pub wallet: Option<i64>,
+    pub kind: AddressKind,
 }
 
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
@@ -62,6 +196,8 @@ pub enum ChainParam {
     EtherScan(AnyScan),
     PolyScan(AnyScan),
     ArbiScan(AnyScan),
+    EvmRpc(EvmRpc),
+    Bitcoin(Bitcoin),
     Cardano(Cardano),
 }
 
@@ -70,6 +206,51 @@ pub enum ChainParam {
 pub struct AnyScan {
     pub base_url: String,
     pub token: String,
+    /// Additional API keys, requests rotate over all of them
+    #[serde(default)]
+    pub tokens: Vec<String>,
+    pub last: u64,
+    /// Minimal delay between requests with one key in milliseconds
+    pub delay: u64,
+    /// Blocks the tip must be ahead of a block before it is processed
+    #[serde(default)]
+    pub confirmations: u64,
+}
+
+/// Ethereum-compatible JSON-RPC node (geth, erigon, anvil...)
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct EvmRpc {
+    pub url: String,
+    pub last: u64,
+    pub delay: u64,
+    /// Blocks the tip must be ahead of a block before it is processed
+    #[serde(default)]
+    pub confirmations: u64,
+    /// Tracing API of the node, internal calls are recorded only with one
+    #[serde(default)]
+    pub trace: EvmTrace,
+}
+
+/// Node API tracing internal calls of the block transactions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub enum EvmTrace {
+    #[default]
+    None,
+    /// `debug_traceBlockByNumber` with the call tracer (geth, erigon...)
+    Debug,
+    /// `trace_block` (openethereum, erigon, nethermind...)
+    Parity,
+}
+
+/// Bitcoin Core compatible RPC node (Bitcoin, Litecoin, Dogecoin...)
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct Bitcoin {
+    pub url: String,
+    pub user: String,
+    pub password: String,
     pub last: u64,
     pub delay: u64,
 }
@@ -77,9 +258,35 @@ pub struct AnyScan {
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 #[cfg_attr(feature = "schema", derive(Schema))]
 pub struct Cardano {
+    /// Node address, `host:port` for TCP otherwise path to Unix socket
     pub address: String,
     pub block_hash: String,
     pub slot: u64,
+    #[serde(default)]
+    pub network: CardanoNetwork,
+    #[serde(default)]
+    pub source: CardanoSource,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub enum CardanoNetwork {
+    #[default]
+    Mainnet,
+    Preprod,
+    Preview,
+    /// Network magic of a custom network
+    Custom(u64),
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub enum CardanoSource {
+    /// Node-to-node protocol, any relay node
+    #[default]
+    N2N,
+    /// Node-to-client protocol, local cardano-node socket
+    N2C,
 }
 
 #[derive(Default, Debug, Deserialize, Serialize, Clone)]
@@ -88,3 +295,196 @@ pub struct StoredList {
     pub description: String,
     pub addresses: Vec<i64>,
 }
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub enum FeedState {
+    #[default]
+    Stopped,
+    Running,
+    /// Feed answers commands but doesn't process blocks
+    Paused,
+    /// Feed didn't answer in time, it is busy or stuck
+    Unresponsive,
+    /// Chain is disabled, its feed is not started
+    Disabled,
+}
+
+/// Inclusive range of block heights to process again
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct Backfill {
+    pub from: u64,
+    pub to: u64,
+}
+
+/// Chain update options, feed progress in the params is stored only on rewind
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct ChainQuery {
+    pub rewind: Option<bool>,
+}
+
+/// Height where the feed continues
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct Rewind {
+    pub height: u64,
+}
+
+/// Progress of the chain feed, times are unix timestamps in seconds
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct FeedStatus {
+    pub chain: i32,
+    pub state: FeedState,
+    /// Last processed block
+    pub height: u64,
+    pub tip: Option<u64>,
+    pub blocks_per_second: f64,
+    pub last_error: Option<String>,
+    pub last_error_time: Option<i64>,
+    pub last_success: Option<i64>,
+    /// Range being processed again, `from` is the next height
+    pub backfill: Option<Backfill>,
+    /// Times the feed crashed and was restarted since the server start
+    pub crashes: u32,
+    pub last_crash: Option<String>,
+    pub last_crash_time: Option<i64>,
+}
+
+/// Movement of one asset through one address
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct AssetFlow {
+    pub id: i64,
+    pub hex: String,
+    #[serde(default)]
+    pub native: String,
+    pub human: String,
+    pub asset: Asset,
+    /// Decimal numbers in the smallest unit of the asset
+    pub received: String,
+    pub sent: String,
+    pub balance: String,
+    pub transactions: i64,
+}
+
+/// Holders and movers of assets under one Cardano policy
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct PolicyRelation {
+    pub policy: String,
+    /// Addresses with positive balance, largest first
+    pub holders: Vec<AssetFlow>,
+    /// Addresses with most transactions first
+    pub movers: Vec<AssetFlow>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct ListQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub enum JobState {
+    #[default]
+    Queued = 0,
+    Running = 1,
+    Done = 2,
+    Failed = 3,
+    Cancelled = 4,
+}
+
+impl JobState {
+    /// State stored in database
+    pub fn from_i32(state: i32) -> Self {
+        match state {
+            1 => JobState::Running,
+            2 => JobState::Done,
+            3 => JobState::Failed,
+            4 => JobState::Cancelled,
+            _ => JobState::Queued,
+        }
+    }
+}
+
+/// Address crawl request, times are unix timestamps in seconds
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct Job {
+    pub id: i64,
+    pub chain: i32,
+    pub address: i64,
+    pub state: JobState,
+    pub attempts: i32,
+    pub error: Option<String>,
+    pub created: i64,
+    pub started: Option<i64>,
+    pub finished: Option<i64>,
+    /// Failed job queued again waits until then
+    pub not_before: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct JobRequest {
+    pub addresses: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct JobQuery {
+    pub chain: Option<i32>,
+    pub state: Option<JobState>,
+    pub limit: Option<i64>,
+}
+
+/// Format of the transaction dump
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub enum ImportFormat {
+    #[default]
+    Jsonl,
+    Csv,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct ImportQuery {
+    pub format: Option<ImportFormat>,
+    pub dry_run: Option<bool>,
+}
+
+/// Result of the transaction dump import, dry run only counts what would be stored
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct ImportReport {
+    pub rows: u64,
+    pub invalid: u64,
+    pub transactions: u64,
+    pub existing: u64,
+    pub errors: Vec<String>,
+    pub dry_run: bool,
+}
+
+/// Result of the service registry sync, invalid files are skipped
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct ServiceSync {
+    pub files: u64,
+    pub services: u64,
+    pub hashes: u64,
+    pub errors: Vec<String>,
+}
+
+/// Addresses touched by the service during the full re-mark
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(Schema))]
+pub struct ServiceMark {
+    pub service: i32,
+    pub title: String,
+    pub addresses: u64,
+}
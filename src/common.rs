@@ -20,6 +20,7 @@ pub enum DirectionOfInteraction {
     From,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn address_interacting(
     db: DatabaseConnection,
     chains: Vec<Chain>,
@@ -27,8 +28,15 @@ pub async fn address_interacting(
     addresses_to: Option<Vec<i64>>,
     services: Option<Vec<i32>>,
     tags: Option<Vec<i32>>,
+    descendants: bool,
     direction: DirectionOfInteraction,
 ) -> Result<BTreeSet<i64>, String> {
+    // Tag matches its whole subtree
+    let tags = match tags {
+        Some(t) if descendants => Some(crate::tag::descendants(&db, &t).await?),
+        tags => tags,
+    };
+
     let sql = {
         let sql_services = match &services {
             Some(s) if !s.is_empty() => "AND A.services && $2",
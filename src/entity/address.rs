@@ -12,6 +12,11 @@ pub struct Model {
     pub title: Option<String>,
     pub services: Vec<i32>, // TODO: Replace for Service
     pub tags: Vec<i32>,     // TODO: Replace for Tag
+    pub block_id: Option<i64>,
+    pub payment: Option<Vec<u8>>,
+    pub delegation: Option<Vec<u8>>,
+    pub wallet_id: Option<i64>,
+    pub kind: i32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -24,6 +29,26 @@ pub enum Relation {
         on_delete = "Cascade"
     )]
     Chain,
+    #[sea_orm(
+        belongs_to = "super::block::Entity",
+        from = "Column::BlockId",
+        to = "super::block::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Block,
+    #[sea_orm(
+        belongs_to = "super::wallet::Entity",
+        from = "Column::WalletId",
+        to = "super::wallet::Column::Id",
+        on_update = "NoAction",
+        on_delete = "SetNull"
+    )]
+    Wallet,
+    #[sea_orm(has_many = "super::job::Entity")]
+    Job,
+    #[sea_orm(has_many = "super::transfer::Entity")]
+    Transfer,
 }
 
 impl Related<super::chain::Entity> for Entity {
@@ -32,4 +57,28 @@ impl Related<super::chain::Entity> for Entity {
     }
 }
 
+impl Related<super::block::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Block.def()
+    }
+}
+
+impl Related<super::wallet::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Wallet.def()
+    }
+}
+
+impl Related<super::job::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Job.def()
+    }
+}
+
+impl Related<super::transfer::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Transfer.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -9,14 +9,27 @@ pub struct Model {
     pub id: i32,
     pub title: String,
     pub params: Json,
+    pub disabled: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(has_many = "super::address::Entity")]
     Address,
+    #[sea_orm(has_many = "super::asset::Entity")]
+    Asset,
+    #[sea_orm(has_many = "super::block::Entity")]
+    Block,
+    #[sea_orm(has_many = "super::import::Entity")]
+    Import,
+    #[sea_orm(has_many = "super::job::Entity")]
+    Job,
+    #[sea_orm(has_many = "super::service_hash::Entity")]
+    ServiceHash,
     #[sea_orm(has_many = "super::transaction::Entity")]
     Transaction,
+    #[sea_orm(has_many = "super::wallet::Entity")]
+    Wallet,
 }
 
 impl Related<super::address::Entity> for Entity {
@@ -25,10 +38,46 @@ impl Related<super::address::Entity> for Entity {
     }
 }
 
+impl Related<super::asset::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Asset.def()
+    }
+}
+
+impl Related<super::block::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Block.def()
+    }
+}
+
+impl Related<super::import::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Import.def()
+    }
+}
+
+impl Related<super::job::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Job.def()
+    }
+}
+
+impl Related<super::service_hash::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ServiceHash.def()
+    }
+}
+
 impl Related<super::transaction::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Transaction.def()
     }
 }
 
+impl Related<super::wallet::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Wallet.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -3,7 +3,14 @@
 pub mod prelude;
 
 pub mod address;
+pub mod asset;
+pub mod block;
 pub mod chain;
+pub mod import;
+pub mod job;
 pub mod service;
+pub mod service_hash;
 pub mod tag;
 pub mod transaction;
+pub mod transfer;
+pub mod wallet;
@@ -1,7 +1,14 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.4
 
 pub use super::address::Entity as AddressEntity;
+pub use super::asset::Entity as Asset;
+pub use super::block::Entity as Block;
 pub use super::chain::Entity as Chain;
+pub use super::import::Entity as Import;
+pub use super::job::Entity as Job;
 pub use super::service::Entity as ServiceEntity;
+pub use super::service_hash::Entity as ServiceHash;
 pub use super::tag::Entity as Tag;
 pub use super::transaction::Entity as Transaction;
+pub use super::transfer::Entity as Transfer;
+pub use super::wallet::Entity as Wallet;
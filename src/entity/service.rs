@@ -8,9 +8,22 @@ pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
     pub title: String,
+    #[sea_orm(column_type = "Text")]
+    pub description: String,
+    pub url: Option<String>,
+    pub tags: Vec<i32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(has_many = "super::service_hash::Entity")]
+    ServiceHash,
+}
+
+impl Related<super::service_hash::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ServiceHash.def()
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
@@ -9,9 +9,11 @@ pub struct Model {
     pub id: i64,
     pub chain: i32,
     pub hash: Vec<u8>,
-    pub amount: Option<u32>,
     pub from: Vec<i64>,
     pub to: Vec<i64>,
+    pub block_id: Option<i64>,
+    pub timestamp: Option<DateTimeWithTimeZone>,
+    pub position: Option<i32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -24,6 +26,16 @@ pub enum Relation {
         on_delete = "Cascade"
     )]
     Chain,
+    #[sea_orm(
+        belongs_to = "super::block::Entity",
+        from = "Column::BlockId",
+        to = "super::block::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Block,
+    #[sea_orm(has_many = "super::transfer::Entity")]
+    Transfer,
 }
 
 impl Related<super::chain::Entity> for Entity {
@@ -32,4 +44,16 @@ impl Related<super::chain::Entity> for Entity {
     }
 }
 
+impl Related<super::block::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Block.def()
+    }
+}
+
+impl Related<super::transfer::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Transfer.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -2,13 +2,78 @@ use crate::feed::Feed;
 use async_trait::async_trait;
 use rweb::http::request;
 use sea_orm::{entity::*, query::*, ActiveModelTrait, DatabaseConnection, Set, Unset};
+use std::collections::BTreeMap;
 
-fn to_transaction(transactions: &Vec<serde_json::Value>) -> super::TransactionList {
+/// *Scan returns hex numbers from proxy module and decimal ones from account module
+fn number(value: &serde_json::Value) -> Option<i64> {
+    let value = value.as_str()?;
+    if let Some(hex) = value.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()
+    } else {
+        value.parse().ok()
+    }
+}
+
+/// Value of any size, hex from proxy module and decimal from account module
+fn value(value: &serde_json::Value) -> Option<String> {
+    let value = value.as_str()?;
+    if value.starts_with("0x") {
+        super::hex_to_decimal(value)
+    } else if !value.is_empty() && value.chars().all(|c| c.is_ascii_digit()) {
+        Some(value.to_string())
+    } else {
+        None
+    }
+}
+
+/// Blocks referenced by account module transactions, internal ones have no block hash
+fn to_block(transactions: &[serde_json::Value]) -> Vec<super::Block> {
+    transactions
+        .iter()
+        .filter_map(|t| {
+            Some(super::Block {
+                height: number(&t["blockNumber"])? as u64,
+                slot: None,
+                hash: hex::decode(t["blockHash"].as_str()?.trim_start_matches("0x")).ok()?,
+                timestamp: number(&t["timeStamp"]),
+            })
+        })
+        .collect()
+}
+
+fn to_transaction(
+    transactions: &[serde_json::Value],
+    block_map: &BTreeMap<Vec<u8>, i64>,
+    timestamp: Option<i64>,
+) -> super::TransactionList {
     transactions
         .iter()
         .map(|t| {
-            (
-                hex::decode(
+            // Internal transactions share the hash, the trace address tells the call
+            let index = match t["traceId"].as_str() {
+                Some(trace) => super::trace_index(trace),
+                None => 0,
+            };
+            let from = vec![hex::decode(
+                t["from"]
+                    .as_str()
+                    .unwrap_or("0x0000")
+                    .chars()
+                    .skip(2)
+                    .collect::<String>(),
+            )
+            .unwrap()];
+            let to = vec![hex::decode(
+                t["to"]
+                    .as_str()
+                    .unwrap_or("0x0000")
+                    .chars()
+                    .skip(2)
+                    .collect::<String>(),
+            )
+            .unwrap()];
+            super::Transaction {
+                hash: hex::decode(
                     &t["hash"]
                         .as_str()
                         .unwrap_or("0000")
@@ -17,87 +82,310 @@ fn to_transaction(transactions: &Vec<serde_json::Value>) -> super::TransactionLi
                         .collect::<String>(),
                 )
                 .unwrap(),
-                if let Some(value) = t["value"].as_str() {
-                    Some(
-                        u128::from_str_radix(&value.chars().skip(2).collect::<String>(), 16)
-                            .unwrap_or(0),
-                    )
+                transfers: if index > 0 {
+                    super::internal_transfers(&from[0], &to[0], value(&t["value"]), index)
                 } else {
-                    None
+                    super::native_transfers(&from, &to, value(&t["value"]), index)
                 },
-                vec![hex::decode(
-                    t["from"]
-                        .as_str()
-                        .unwrap_or("0x0000")
-                        .chars()
-                        .skip(2)
-                        .collect::<String>(),
-                )
-                .unwrap()],
-                vec![hex::decode(
-                    t["to"]
-                        .as_str()
-                        .unwrap_or("0x0000")
-                        .chars()
-                        .skip(2)
-                        .collect::<String>(),
-                )
-                .unwrap()],
-            )
+                from,
+                to,
+                block: t["blockHash"]
+                    .as_str()
+                    .and_then(|h| hex::decode(h.trim_start_matches("0x")).ok())
+                    .and_then(|h| block_map.get(&h).cloned()),
+                timestamp: number(&t["timeStamp"]).or(timestamp),
+                position: number(&t["transactionIndex"]).map(|i| i as i32),
+            }
         })
         .collect()
 }
 
+/// Token transfers from account module, the sender of the transaction is unknown
+fn to_token(
+    transactions: &[serde_json::Value],
+    block_map: &BTreeMap<Vec<u8>, i64>,
+    kind: shared::AssetKind,
+) -> super::TransactionList {
+    let decode = |value: &serde_json::Value| -> Option<Vec<u8>> {
+        hex::decode(value.as_str()?.trim_start_matches("0x")).ok()
+    };
+
+    transactions
+        .iter()
+        .filter_map(|t| {
+            let hash = decode(&t["hash"])?;
+            let (name, amount) = match kind {
+                shared::AssetKind::Erc721 => (
+                    super::decimal_to_bytes(t["tokenID"].as_str()?)?,
+                    "1".to_string(),
+                ),
+                shared::AssetKind::Erc1155 => (
+                    super::decimal_to_bytes(t["tokenID"].as_str()?)?,
+                    value(&t["tokenValue"])?,
+                ),
+                _ => (Vec::new(), value(&t["value"])?),
+            };
+            let asset = super::Asset {
+                kind,
+                contract: decode(&t["contractAddress"])?,
+                name,
+            };
+            // Block ingest stores the transfer under its log index, records
+            // without it would be stored twice
+            let index = number(&t["logIndex"])? as i32;
+
+            Some(super::Transaction {
+                transfers: super::token::pair(
+                    decode(&t["from"])?,
+                    decode(&t["to"])?,
+                    asset,
+                    index,
+                    amount,
+                ),
+                hash,
+                from: Vec::new(),
+                to: Vec::new(),
+                block: decode(&t["blockHash"]).and_then(|h| block_map.get(&h).cloned()),
+                timestamp: number(&t["timeStamp"]),
+                position: number(&t["transactionIndex"]).map(|i| i as i32),
+            })
+        })
+        .collect()
+}
+
+/// Token transfer logs of the block with the given event signature
+async fn logs(
+    any: &shared::AnyScan,
+    block: u64,
+    topic: &str,
+) -> Result<Vec<serde_json::Value>, String> {
+    let mut logs = Vec::new();
+    let mut page = 1;
+
+    loop {
+        let query = format!(
+            "module=logs&action=getLogs&fromBlock={}&toBlock={}&topic0={}&page={}&offset={}",
+            block,
+            block,
+            topic,
+            page,
+            super::scan::PAGE_SIZE
+        );
+        let result = super::scan::request(any, &query).await?;
+        let result = result.as_array().cloned().unwrap_or_default();
+        let full = result.len() == super::scan::PAGE_SIZE;
+        logs.extend(result);
+
+        if !full {
+            return Ok(logs);
+        }
+        page += 1;
+    }
+}
+
+/// Kinds known from the account module action, normal transactions are signed
+/// by keys while internal ones and token transfers are made by contracts
+fn to_kinds(
+    action: &str,
+    transactions: &[serde_json::Value],
+) -> BTreeMap<Vec<u8>, shared::AddressKind> {
+    // Contract address is empty unless the transaction created one
+    let decode = |value: &serde_json::Value| -> Option<Vec<u8>> {
+        hex::decode(value.as_str()?.trim_start_matches("0x"))
+            .ok()
+            .filter(|a| !a.is_empty())
+    };
+
+    let mut kinds = BTreeMap::new();
+    for t in transactions.iter() {
+        match action {
+            "txlist" => {
+                if let Some(from) = decode(&t["from"]) {
+                    kinds.insert(from, shared::AddressKind::Key);
+                }
+            }
+            "txlistinternal" => {
+                if let Some(from) = decode(&t["from"]) {
+                    kinds.insert(from, shared::AddressKind::Contract);
+                }
+            }
+            _ => {}
+        }
+        if let Some(contract) = decode(&t["contractAddress"]) {
+            kinds.insert(contract, shared::AddressKind::Contract);
+        }
+    }
+    kinds
+}
+
 async fn action(
     any: &mut shared::AnyScan,
     db: &DatabaseConnection,
     address: &crate::entity::address::Model,
     action: &str,
-) {
+) -> Result<(), String> {
     let mut block = 0;
-    let mut start: tokio::time::Instant;
+    let mut page = 1;
 
     loop {
-        let url = format!(
-            "{}?module=account&action={}&address=0x{}&startblock={}&apikey={}",
-            any.base_url,
+        let query = format!(
+            "module=account&action={}&address=0x{}&startblock={}&page={}&offset={}&sort=asc",
             action,
             hex::encode(&address.hash),
             block,
-            any.token
+            page,
+            super::scan::PAGE_SIZE
         );
-        tracing::info!("AnyScan Address: {}", url);
-        start = tokio::time::Instant::now();
-
-        if let Ok(request) = reqwest::get(url).await {
-            if let Ok(body) = request.json::<serde_json::Value>().await {
-                if let Some(transactions) = body["result"].as_array() {
-                    any.add_transactions(
-                        db,
-                        address.chain.clone(),
-                        // Iterate over result values
-                        to_transaction(transactions),
-                    )
-                    .await;
-
-                    // Id the result is full,
-                    if transactions.len() == 10000 {
-                        block = transactions
-                            .iter()
-                            .map(|t| {
-                                t["blockNumber"]
-                                    .as_str()
-                                    .unwrap_or(&block.to_string())
-                                    .parse::<u64>()
-                                    .unwrap_or(block)
-                            })
-                            .fold(block + 1, |max, i| std::cmp::max(max, i));
-                        continue;
-                    }
-                }
-            }
+        let result = super::scan::request(any, &query).await?;
+        let transactions = match result.as_array() {
+            Some(transactions) => transactions,
+            None => return Err(format!("{}: unexpected result {}", action, result)),
+        };
+
+        let block_map = any
+            .add_blocks(db, address.chain, to_block(transactions))
+            .await?;
+        let transaction_list = match action {
+            "tokentx" => to_token(transactions, &block_map, shared::AssetKind::Erc20),
+            "tokennfttx" => to_token(transactions, &block_map, shared::AssetKind::Erc721),
+            "token1155tx" => to_token(transactions, &block_map, shared::AssetKind::Erc1155),
+            // Iterate over result values
+            _ => to_transaction(transactions, &block_map, None),
+        };
+        any.add_transactions(db, address.chain, transaction_list)
+            .await?;
+        any.add_kinds(db, address.chain, to_kinds(action, transactions))
+            .await;
+
+        if transactions.len() < super::scan::PAGE_SIZE {
+            return Ok(());
+        }
+
+        // Continue from the last block, it may be returned only partially. Results
+        // are limited to a window of pages, so the block moves whenever possible.
+        let last = transactions
+            .iter()
+            .filter_map(|t| number(&t["blockNumber"]))
+            .max()
+            .unwrap_or(block);
+        if last > block {
+            block = last;
+            page = 1;
+        } else {
+            page += 1;
         }
-        break;
+    }
+}
+
+/// Hash of the block of the given height known to *Scan
+async fn block_hash(any: &shared::AnyScan, height: u64) -> Result<Option<Vec<u8>>, String> {
+    let query = format!(
+        "module=proxy&action=eth_getBlockByNumber&tag={:#0x}&boolean=false",
+        height
+    );
+    let result = super::scan::request(any, &query).await?;
+    Ok(result["hash"]
+        .as_str()
+        .and_then(|h| hex::decode(h.trim_start_matches("0x")).ok()))
+}
+
+/// Store block of the given height if it continues the stored chain
+async fn ingest(
+    any: &mut shared::AnyScan,
+    db: &DatabaseConnection,
+    chain_id: i32,
+    height: u64,
+) -> Result<super::reorg::Ingest, String> {
+    // Query string for *Scan apis
+    let query = format!(
+        "module=proxy&action=eth_getBlockByNumber&tag={:#0x}&boolean=true",
+        height
+    );
+
+    let result = match super::scan::request(any, &query).await {
+        Ok(result) if result.is_object() => result,
+        Ok(_) => return Ok(super::reorg::Ingest::Unavailable),
+        Err(err) => return Err(format!("AnyScan block {}: {}", height, err)),
+    };
+
+    let parent = result["parentHash"]
+        .as_str()
+        .and_then(|h| hex::decode(h.trim_start_matches("0x")).ok());
+    if !super::reorg::continues(db, chain_id, height, parent.as_deref()).await? {
+        return Ok(super::reorg::Ingest::Orphaned);
+    }
+
+    // Block is stored only complete, missing logs would leave a gap
+    let mut block_logs = Vec::new();
+    for topic in [
+        super::token::TRANSFER,
+        super::token::TRANSFER_SINGLE,
+        super::token::TRANSFER_BATCH,
+    ] {
+        match logs(any, height, topic).await {
+            Ok(logs) => block_logs.extend(logs),
+            Err(err) => return Err(format!("AnyScan logs {}: {}", height, err)),
+        }
+    }
+
+    let timestamp = number(&result["timestamp"]);
+    let mut block_map = BTreeMap::new();
+    if let Some(hash) = result["hash"]
+        .as_str()
+        .and_then(|h| hex::decode(h.trim_start_matches("0x")).ok())
+    {
+        block_map = any
+            .add_blocks(
+                db,
+                chain_id,
+                vec![super::Block {
+                    height,
+                    slot: None,
+                    hash,
+                    timestamp,
+                }],
+            )
+            .await?;
+    }
+
+    if let Some(transactions) = result["transactions"].as_array() {
+        // Iterate over result values
+        let mut transaction_list = to_transaction(transactions, &block_map, timestamp);
+        super::token::attach(&mut transaction_list, &block_logs);
+
+        any.add_transactions(db, chain_id, transaction_list).await?;
+        any.add_kinds(db, chain_id, to_kinds("txlist", transactions))
+            .await;
+    }
+    Ok(super::reorg::Ingest::Stored)
+}
+
+/// Store the next block height to chain params
+async fn save(
+    any: &shared::AnyScan,
+    db: &DatabaseConnection,
+    chain: &mut crate::entity::chain::Model,
+) -> Result<(), String> {
+    let mut params: shared::ChainParam = serde_json::from_value(chain.params.clone()).unwrap();
+    match &mut params {
+        shared::ChainParam::ArbiScan(arbi) => arbi.last = any.last,
+        shared::ChainParam::EtherScan(ether) => ether.last = any.last,
+        shared::ChainParam::PolyScan(ether) => ether.last = any.last,
+        _ => {}
+    }
+    let chain_update = crate::entity::chain::ActiveModel {
+        id: Set(chain.id),
+        params: Set(serde_json::to_value(&params).unwrap()),
+        title: Unset(None),
+        disabled: NotSet,
+    };
+
+    if chain_update.update(db).await.is_ok() {
+        tracing::info!("Chain updated");
+        chain.params = serde_json::to_value(params).unwrap();
+        Ok(())
+    } else {
+        Err(String::from("Chain not updated"))
     }
 }
 
@@ -114,65 +402,77 @@ impl super::Feed for shared::AnyScan {
         }
     }
 
+    // Client backs off on its own
+    async fn fail(&mut self) {}
+
     async fn process_block(
         &mut self,
         db: &DatabaseConnection,
         chain: &mut crate::entity::chain::Model,
-    ) {
-        // Query string for *Scan apis
-        let url = format!(
-            "{}?module=proxy&action=eth_getBlockByNumber&tag={:#0x}&boolean=true&apikey={}",
-            self.base_url, self.last, self.token
-        );
-        tracing::info!("AnyScan block: {}", url);
-
-        if let Ok(request) = reqwest::get(url).await {
-            if let Ok(body) = request.json::<serde_json::Value>().await {
-                if let Some(result) = body["result"].as_object() {
-                    if let Some(transactions) = result["transactions"].as_array() {
-                        self.add_transactions(
-                            db,
-                            chain.id.clone(),
-                            // Iterate over result values
-                            to_transaction(transactions),
-                        )
-                        .await;
-                    }
-
-                    if let Some(last) = result["number"].as_str() {
-                        self.last = u64::from_str_radix(&last[2..], 16).unwrap() + 1;
-
-                        let mut params: shared::ChainParam =
-                            serde_json::from_value(chain.params.clone()).unwrap();
-                        match &mut params {
-                            shared::ChainParam::ArbiScan(arbi) => arbi.last = self.last.clone(),
-                            shared::ChainParam::EtherScan(ether) => ether.last = self.last.clone(),
-                            shared::ChainParam::PolyScan(ether) => ether.last = self.last.clone(),
-                            _ => {}
-                        }
-                        let chain_update = crate::entity::chain::ActiveModel {
-                            id: Set(chain.id.clone()),
-                            params: Set(serde_json::to_value(params).unwrap()),
-                            title: Unset(None),
-                        };
-
-                        if let Ok(c) = chain_update.update(db).await {
-                            tracing::info!("Chain updated");
-                            chain.params = serde_json::to_value(self.clone()).unwrap().clone();
-                        } else {
-                            tracing::info!("Chain not updated");
-                        }
-                    }
-                }
-            } else {
-                tracing::error!("Problem with deserialize");
+    ) -> Result<(), String> {
+        let (height, confirmations) = (self.last, self.confirmations);
+        if !super::reorg::confirmed(self, chain.id, height, confirmations).await? {
+            tracing::info!("AnyScan block {} not confirmed yet", height);
+            return Ok(());
+        }
+
+        match ingest(self, db, chain.id, height).await? {
+            super::reorg::Ingest::Stored => self.last += 1,
+            super::reorg::Ingest::Unavailable => {
+                tracing::info!("AnyScan block {} not available yet", height);
+                return Ok(());
+            }
+            super::reorg::Ingest::Orphaned => {
+                let any = &*self;
+                let fork = super::reorg::fork(db, chain.id, height, |h| block_hash(any, h)).await?;
+                self.last = fork + 1;
             }
-        } else {
-            tracing::error!("Problem with request");
         }
+        save(self, db, chain).await
     }
 
-    async fn process_address(&mut self, db: &DatabaseConnection, address: i64) {
+    async fn rewind(
+        &mut self,
+        db: &DatabaseConnection,
+        chain: &mut crate::entity::chain::Model,
+        height: u64,
+    ) -> Result<(), String> {
+        self.last = height;
+        save(self, db, chain).await
+    }
+
+    async fn backfill(
+        &mut self,
+        db: &DatabaseConnection,
+        chain: &mut crate::entity::chain::Model,
+        height: u64,
+    ) -> Result<(), String> {
+        match ingest(self, db, chain.id, height).await? {
+            super::reorg::Ingest::Stored => Ok(()),
+            super::reorg::Ingest::Unavailable => {
+                Err(format!("AnyScan block {} not available", height))
+            }
+            super::reorg::Ingest::Orphaned => Err(format!(
+                "AnyScan block {} doesn't continue the stored chain, rewind instead",
+                height
+            )),
+        }
+    }
+
+    fn height(&self) -> u64 {
+        self.last.saturating_sub(1)
+    }
+
+    async fn tip(&mut self) -> Result<Option<u64>, String> {
+        let result = super::scan::request(self, "module=proxy&action=eth_blockNumber").await?;
+        Ok(number(&result).map(|n| n as u64))
+    }
+
+    async fn process_address(
+        &mut self,
+        db: &DatabaseConnection,
+        address: i64,
+    ) -> Result<(), String> {
         if let Ok(Some(address)) = crate::entity::address::Entity::find_by_id(address)
             .column(crate::entity::address::Column::Hash)
             .column(crate::entity::address::Column::Chain)
@@ -180,10 +480,27 @@ impl super::Feed for shared::AnyScan {
             .await
         {
             // Query string for *Scan apis
-            action(self, db, &address, "txlist").await;
-            action(self, db, &address, "txlistinternal").await;
+            let mut errors = Vec::new();
+            for name in [
+                "txlist",
+                "txlistinternal",
+                "tokentx",
+                "tokennfttx",
+                "token1155tx",
+            ] {
+                if let Err(err) = action(self, db, &address, name).await {
+                    tracing::error!("AnyScan address {}: {}", address.id, err);
+                    errors.push(err);
+                }
+            }
+
+            if errors.is_empty() {
+                Ok(())
+            } else {
+                Err(errors.join("; "))
+            }
         } else {
-            tracing::error!("Address not found!!!!");
+            Err(format!("Address {} not found", address))
         }
     }
 }
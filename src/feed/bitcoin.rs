@@ -0,0 +1,520 @@
+use crate::feed::Feed;
+use async_trait::async_trait;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, NotSet, Set};
+use std::collections::BTreeMap;
+
+/// RPC error code of a block height above the node tip
+const OUT_OF_RANGE: i64 = -8;
+
+/// Call Bitcoin Core compatible RPC method, the response carries either its
+/// `result` or `error`
+async fn call(
+    bitcoin: &shared::Bitcoin,
+    method: &str,
+    params: serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let body = serde_json::json!({
+        "jsonrpc": "1.0",
+        "id": "car",
+        "method": method,
+        "params": params,
+    });
+
+    let mut request = reqwest::Client::new().post(&bitcoin.url).json(&body);
+    if !bitcoin.user.is_empty() {
+        request = request.basic_auth(&bitcoin.user, Some(&bitcoin.password));
+    }
+
+    // Node responds with error status together with JSON body
+    let text = request
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .text()
+        .await
+        .map_err(|e| e.to_string())?;
+    serde_json::from_str(&quote_amounts(&text)).map_err(|e| format!("{}: {}", method, e))
+}
+
+/// Result of Bitcoin Core compatible RPC method, node error is returned as `Err`
+async fn request(
+    bitcoin: &shared::Bitcoin,
+    method: &str,
+    params: serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let body = call(bitcoin, method, params).await?;
+    if !body["error"].is_null() {
+        return Err(format!("{}: {}", method, body["error"]));
+    }
+    Ok(body["result"].clone())
+}
+
+/// Amounts of the `value` fields are turned into strings with the exact
+/// number text, larger amounts don't fit into f64 with all decimals
+fn quote_amounts(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut string = String::new();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        result.push(c);
+        if in_string {
+            match c {
+                '\\' => {
+                    if let Some(escaped) = chars.next() {
+                        result.push(escaped);
+                        string.push(escaped);
+                    }
+                }
+                '"' => in_string = false,
+                _ => string.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                string.clear();
+            }
+            ':' if string == "value" => {
+                while let Some(w) = chars.next_if(|w| w.is_whitespace()) {
+                    result.push(w);
+                }
+                if matches!(chars.peek(), Some(n) if *n == '-' || n.is_ascii_digit()) {
+                    result.push('"');
+                    while let Some(n) =
+                        chars.next_if(|n| "+-.eE".contains(*n) || n.is_ascii_digit())
+                    {
+                        result.push(n);
+                    }
+                    result.push('"');
+                }
+                string.clear();
+            }
+            _ if !c.is_whitespace() => string.clear(),
+            _ => {}
+        }
+    }
+    result
+}
+
+/// Satoshis of the amount as the node prints it, `1.5` is `150000000`
+fn satoshis(amount: &str) -> Option<String> {
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    if whole.is_empty()
+        || fraction.len() > 8
+        || !whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    let digits = format!("{}{:0<8}", whole, fraction);
+    match digits.trim_start_matches('0') {
+        "" => Some("0".to_string()),
+        digits => Some(digits.to_string()),
+    }
+}
+
+/// Get block header together with decoded block transactions
+async fn block_transactions(
+    bitcoin: &shared::Bitcoin,
+    hash: &str,
+) -> Result<(serde_json::Value, Vec<serde_json::Value>), String> {
+    match request(bitcoin, "getblock", serde_json::json!([hash, 2])).await {
+        Ok(block) => {
+            let transactions = block["tx"].as_array().cloned().unwrap_or_default();
+            Ok((block, transactions))
+        }
+        Err(_) => {
+            // Dogecoin knows only verbose flag, transactions are fetched one by one
+            let block = request(bitcoin, "getblock", serde_json::json!([hash, true])).await?;
+            let mut transactions = Vec::new();
+            for txid in block["tx"].as_array().into_iter().flatten() {
+                transactions.push(
+                    request(bitcoin, "getrawtransaction", serde_json::json!([txid, 1])).await?,
+                );
+            }
+            Ok((block, transactions))
+        }
+    }
+}
+
+/// Output script is used as an address, it is known for every output
+fn script(output: &serde_json::Value) -> Vec<u8> {
+    output["scriptPubKey"]["hex"]
+        .as_str()
+        .and_then(|s| hex::decode(s).ok())
+        .unwrap_or_default()
+}
+
+/// Spent outputs as (transaction hash, output index), coinbase is skipped
+fn inputs(transaction: &serde_json::Value) -> Vec<(Vec<u8>, i64, &serde_json::Value)> {
+    transaction["vin"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|i| {
+            Some((
+                hex::decode(i["txid"].as_str()?).ok()?,
+                i["vout"].as_i64()?,
+                i,
+            ))
+        })
+        .collect()
+}
+
+/// Output scripts in output order
+fn outputs(transaction: &serde_json::Value) -> Vec<Vec<u8>> {
+    let mut outputs = transaction["vout"].as_array().cloned().unwrap_or_default();
+    outputs.sort_by_key(|o| o["n"].as_u64());
+    outputs.iter().map(script).collect()
+}
+
+/// Output values in satoshis in output order
+fn values(transaction: &serde_json::Value) -> Vec<String> {
+    let mut outputs = transaction["vout"].as_array().cloned().unwrap_or_default();
+    outputs.sort_by_key(|o| o["n"].as_u64());
+    outputs
+        .iter()
+        // Node reports whole coins with 8 decimals
+        .map(|o| {
+            o["value"]
+                .as_str()
+                .and_then(satoshis)
+                .unwrap_or_else(|| "0".to_string())
+        })
+        .collect()
+}
+
+/// Store block of the given height, returns false if the node doesn't have it yet
+async fn ingest(
+    bitcoin: &mut shared::Bitcoin,
+    db: &DatabaseConnection,
+    chain_id: i32,
+    height: u64,
+) -> Result<bool, String> {
+    tracing::info!("Bitcoin block: {}", height);
+
+    // Only a height above the tip means the block isn't there yet
+    let body = call(bitcoin, "getblockhash", serde_json::json!([height]))
+        .await
+        .map_err(|err| format!("Bitcoin block {}: {}", height, err))?;
+    if body["error"]["code"].as_i64() == Some(OUT_OF_RANGE) {
+        return Ok(false);
+    }
+    let hash = match (&body["error"], &body["result"]) {
+        (serde_json::Value::Null, serde_json::Value::String(hash)) => hash.clone(),
+        (serde_json::Value::Null, result) => {
+            return Err(format!("Bitcoin block {}: invalid hash {}", height, result))
+        }
+        (error, _) => return Err(format!("Bitcoin block {}: getblockhash: {}", height, error)),
+    };
+
+    let (block, transactions) = match block_transactions(bitcoin, &hash).await {
+        Ok(result) => result,
+        Err(err) => return Err(format!("Bitcoin block {}: {}", height, err)),
+    };
+
+    let timestamp = block["time"].as_i64();
+    let block_id = bitcoin
+        .add_block(
+            db,
+            chain_id,
+            super::Block {
+                height,
+                slot: None,
+                hash: hex::decode(&hash).unwrap_or_default(),
+                timestamp,
+            },
+        )
+        .await;
+
+    // Outputs created in this block
+    let mut block_outputs: BTreeMap<Vec<u8>, Vec<Vec<u8>>> = BTreeMap::new();
+    for transaction in transactions.iter() {
+        // Transaction without valid hash is skipped as it can't be stored
+        if let Some(txid) = transaction["txid"]
+            .as_str()
+            .and_then(|txid| hex::decode(txid).ok())
+        {
+            block_outputs.insert(txid, outputs(transaction));
+        }
+    }
+
+    // Inputs already known in the database
+    let input_address_map = bitcoin
+        .map_inputs(
+            db,
+            chain_id,
+            &transactions
+                .iter()
+                .flat_map(inputs)
+                .map(|(hash, index, _)| (hash, index))
+                .collect::<Vec<(Vec<u8>, i64)>>(),
+        )
+        .await;
+
+    // Resolve the rest from this block, the input itself or the node
+    let mut input_script_map: BTreeMap<(Vec<u8>, i64), Vec<u8>> = BTreeMap::new();
+    let mut previous: BTreeMap<Vec<u8>, Vec<Vec<u8>>> = BTreeMap::new();
+    for (hash, index, input) in transactions.iter().flat_map(inputs) {
+        let key = (hash.clone(), index);
+        if input_address_map.contains_key(&key) {
+            continue;
+        }
+
+        if let Some(scripts) = block_outputs.get(&hash) {
+            if let Some(script) = scripts.get(index as usize) {
+                input_script_map.insert(key, script.clone());
+            }
+        } else if !input["prevout"].is_null() {
+            input_script_map.insert(key, script(&input["prevout"]));
+        } else {
+            if !previous.contains_key(&hash) {
+                match request(
+                    bitcoin,
+                    "getrawtransaction",
+                    serde_json::json!([hex::encode(&hash), 1]),
+                )
+                .await
+                {
+                    Ok(transaction) => {
+                        previous.insert(hash.clone(), outputs(&transaction));
+                    }
+                    Err(err) => return Err(format!("Bitcoin input: {}", err)),
+                }
+            }
+            if let Some(script) = previous[&hash].get(index as usize) {
+                input_script_map.insert(key, script.clone());
+            }
+        }
+    }
+
+    // Store all known addresses to database
+    let address_list = block_outputs
+        .values()
+        .flatten()
+        .chain(input_script_map.values())
+        .cloned()
+        .collect::<Vec<Vec<u8>>>();
+    bitcoin
+        .add_addresses(db, chain_id, &address_list, block_id)
+        .await?;
+    let address_map = bitcoin.map_address(db, chain_id, &address_list).await?;
+
+    let transaction_list = transactions
+        .iter()
+        .enumerate()
+        .filter_map(|(position, t)| {
+            let hash = hex::decode(t["txid"].as_str()?).ok()?;
+            let from = inputs(t)
+                .into_iter()
+                .filter_map(|(hash, index, _)| {
+                    let key = (hash, index);
+                    input_address_map
+                        .get(&key)
+                        .cloned()
+                        .or_else(|| address_map.get(input_script_map.get(&key)?).cloned())
+                })
+                .collect();
+            let scripts = block_outputs.get(&hash)?;
+            let to = scripts
+                .iter()
+                .filter_map(|s| address_map.get(s).cloned())
+                .collect();
+            let transfers = scripts
+                .iter()
+                .zip(values(t))
+                .enumerate()
+                .filter_map(|(index, (script, amount))| {
+                    Some(super::Transfer {
+                        address: *address_map.get(script)?,
+                        asset: super::Asset::default(),
+                        output: true,
+                        index: index as i32,
+                        amount,
+                        internal: false,
+                    })
+                })
+                .collect();
+            let spent = inputs(t)
+                .into_iter()
+                .map(|(hash, index, _)| (hash, index))
+                .collect();
+            Some(super::UtxoTransaction {
+                hash,
+                from,
+                to,
+                spent,
+                transfers,
+                block: block_id,
+                timestamp,
+                position: Some(position as i32),
+            })
+        })
+        .collect();
+
+    bitcoin
+        .add_utxo_transactions(db, chain_id, transaction_list)
+        .await?;
+
+    Ok(true)
+}
+
+/// Store the next block height to chain params
+async fn save(
+    bitcoin: &shared::Bitcoin,
+    db: &DatabaseConnection,
+    chain: &mut crate::entity::chain::Model,
+) -> Result<(), String> {
+    let mut params: shared::ChainParam = serde_json::from_value(chain.params.clone()).unwrap();
+    if let shared::ChainParam::Bitcoin(param) = &mut params {
+        param.last = bitcoin.last;
+    }
+    let chain_update = crate::entity::chain::ActiveModel {
+        id: Set(chain.id),
+        params: Set(serde_json::to_value(&params).unwrap()),
+        title: NotSet,
+        disabled: NotSet,
+    };
+
+    if chain_update.update(db).await.is_ok() {
+        chain.params = serde_json::to_value(params).unwrap();
+        Ok(())
+    } else {
+        Err(String::from("Chain not updated"))
+    }
+}
+
+#[async_trait]
+impl super::Feed for shared::Bitcoin {
+    async fn wait(&mut self, start: tokio::time::Instant) {
+        let delay = tokio::time::Instant::now().duration_since(start);
+        if let Some(duration) = tokio::time::Duration::from_millis(self.delay).checked_sub(delay) {
+            tracing::info!("Bitcoin sleeping for: {}", duration.as_millis());
+            tokio::time::sleep(duration).await;
+        }
+    }
+
+    // Own node has no request quota, no need to slow down
+    async fn fail(&mut self) {}
+
+    async fn process_block(
+        &mut self,
+        db: &DatabaseConnection,
+        chain: &mut crate::entity::chain::Model,
+    ) -> Result<(), String> {
+        if !ingest(self, db, chain.id, self.last).await? {
+            tracing::info!("Bitcoin block {} not available yet", self.last);
+            return Ok(());
+        }
+
+        self.last += 1;
+        save(self, db, chain).await
+    }
+
+    async fn rewind(
+        &mut self,
+        db: &DatabaseConnection,
+        chain: &mut crate::entity::chain::Model,
+        height: u64,
+    ) -> Result<(), String> {
+        self.last = height;
+        save(self, db, chain).await
+    }
+
+    async fn backfill(
+        &mut self,
+        db: &DatabaseConnection,
+        chain: &mut crate::entity::chain::Model,
+        height: u64,
+    ) -> Result<(), String> {
+        if ingest(self, db, chain.id, height).await? {
+            Ok(())
+        } else {
+            Err(format!("Bitcoin block {} not available", height))
+        }
+    }
+
+    fn height(&self) -> u64 {
+        self.last.saturating_sub(1)
+    }
+
+    async fn tip(&mut self) -> Result<Option<u64>, String> {
+        let tip = request(self, "getblockcount", serde_json::json!([])).await?;
+        Ok(tip.as_u64())
+    }
+
+    async fn process_address(
+        &mut self,
+        _db: &DatabaseConnection,
+        address: i64,
+    ) -> Result<(), String> {
+        // Node has no per address index
+        Err(format!("Bitcoin can't process address {}", address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_value_numbers() {
+        assert_eq!(
+            quote_amounts(r#"{"value": 0.00000001, "n": 0}"#),
+            r#"{"value": "0.00000001", "n": 0}"#
+        );
+        assert_eq!(
+            quote_amounts(r#"{"vout":[{"value":20999999.97690000,"n":1}]}"#),
+            r#"{"vout":[{"value":"20999999.97690000","n":1}]}"#
+        );
+        // Only numbers under the `value` key are quoted
+        assert_eq!(
+            quote_amounts(r#"{"time": 1231006505, "values": 1.5, "x": "value", "value": "1.0"}"#),
+            r#"{"time": 1231006505, "values": 1.5, "x": "value", "value": "1.0"}"#
+        );
+        // Escaped quote doesn't end the string
+        assert_eq!(
+            quote_amounts(r#"{"a": "\"value\": 1", "value": 2}"#),
+            r#"{"a": "\"value\": 1", "value": "2"}"#
+        );
+    }
+
+    #[test]
+    fn exact_values() {
+        let text = r#"{"vout": [
+            {"value": 0.1, "n": 1},
+            {"value": 20999999.97690000, "n": 0},
+            {"value": 0.00000001, "n": 2}
+        ]}"#;
+        let transaction: serde_json::Value = serde_json::from_str(&quote_amounts(text)).unwrap();
+        assert_eq!(
+            values(&transaction),
+            vec!["2099999997690000", "10000000", "1"]
+        );
+    }
+
+    #[test]
+    fn satoshi_amounts() {
+        assert_eq!(satoshis("1.5"), Some("150000000".to_string()));
+        assert_eq!(satoshis("50.00000000"), Some("5000000000".to_string()));
+        assert_eq!(satoshis("0.00000001"), Some("1".to_string()));
+        assert_eq!(satoshis("0.00000000"), Some("0".to_string()));
+        assert_eq!(satoshis("0"), Some("0".to_string()));
+        assert_eq!(satoshis("21000000"), Some("2100000000000000".to_string()));
+        assert_eq!(
+            satoshis("92233720368.54775808"),
+            Some("9223372036854775808".to_string())
+        );
+        assert_eq!(satoshis("0.000000001"), None);
+        assert_eq!(satoshis("-1.0"), None);
+        assert_eq!(satoshis("1e-8"), None);
+        assert_eq!(satoshis("1.2.3"), None);
+        assert_eq!(satoshis(""), None);
+        assert_eq!(satoshis(".5"), None);
+    }
+}
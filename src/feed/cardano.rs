@@ -3,29 +3,31 @@ use async_trait::async_trait;
 use oura::{
     filters::selection::{self, Predicate},
     mapper,
-    model::{BlockRecord, EventData},
+    model::{Event, EventData, TxOutputRecord},
     pipelining::{FilterProvider, SourceProvider, StageReceiver},
-    sources::{n2n, AddressArg, BearerKind, IntersectArg, MagicArg, PointArg},
+    sources::{n2c, n2n, AddressArg, BearerKind, IntersectArg, MagicArg, PointArg},
     utils::{ChainWellKnownInfo, Utils, WithUtils},
 };
 use pallas_addresses::ShelleyAddress;
-use sea_orm::{entity::*, query::*, ActiveModelTrait, DatabaseConnection, DbBackend, Set, Unset};
-use std::{
-    collections::{BTreeMap, BTreeSet},
-    f32::consts::E,
-    str::FromStr,
-    sync::Arc,
-    thread::JoinHandle,
+use sea_orm::{
+    entity::*, ActiveModelTrait, ConnectionTrait, DatabaseConnection, DbBackend, Set, Statement,
+    Unset,
 };
+use std::{f32::consts::E, str::FromStr, sync::Arc, thread::JoinHandle};
 
 pub fn oura_bootstrap(
-    hash: String,
-    slot: u64,
-    socket: String,
+    cardano: &shared::Cardano,
 ) -> (JoinHandle<()>, JoinHandle<()>, StageReceiver) {
-    let magic = MagicArg::from_str("mainnet").unwrap();
+    let magic = match cardano.network {
+        shared::CardanoNetwork::Mainnet => MagicArg::from_str("mainnet").unwrap(),
+        shared::CardanoNetwork::Preprod => MagicArg::from_str("preprod").unwrap(),
+        shared::CardanoNetwork::Preview => MagicArg::from_str("preview").unwrap(),
+        shared::CardanoNetwork::Custom(magic) => MagicArg(magic),
+    };
 
-    let well_known = ChainWellKnownInfo::try_from_magic(*magic).unwrap();
+    // Custom networks are usually local testnets, testnet values are the closest
+    let well_known = ChainWellKnownInfo::try_from_magic(*magic)
+        .unwrap_or_else(|_| ChainWellKnownInfo::testnet());
 
     let utils = Arc::new(Utils::new(well_known));
 
@@ -36,34 +38,58 @@ pub fn oura_bootstrap(
         ..Default::default()
     };
 
-    let intersect = Some(IntersectArg::Point(PointArg(slot, hash)));
+    let intersect = Some(IntersectArg::Point(PointArg(
+        cardano.slot,
+        cardano.block_hash.clone(),
+    )));
 
-    #[allow(deprecated)]
-    let source_config = n2n::Config {
-        address: if socket.contains(':') {
-            AddressArg(BearerKind::Tcp, socket)
-        } else {
-            AddressArg(BearerKind::Unix, socket)
-        },
-        magic: Some(magic),
-        well_known: None,
-        mapper,
-        since: None,
-        min_depth: 0,
-        intersect,
-        retry_policy: None,
-        finalize: None,
+    let address = if cardano.address.contains(':') {
+        AddressArg(BearerKind::Tcp, cardano.address.clone())
+    } else {
+        AddressArg(BearerKind::Unix, cardano.address.clone())
     };
 
-    let source_setup = WithUtils::new(source_config, utils);
-
-    let check = Predicate::VariantIn(vec![String::from("Block")]);
+    let check = Predicate::VariantIn(vec![String::from("Block"), String::from("RollBack")]);
 
     let filter_setup = selection::Config { check };
 
     tracing::info!("{}", "Attempting to connect to node...");
 
-    let (source_handle, source_rx) = source_setup.bootstrap().unwrap();
+    #[allow(deprecated)]
+    let (source_handle, source_rx) = match cardano.source {
+        shared::CardanoSource::N2N => WithUtils::new(
+            n2n::Config {
+                address,
+                magic: Some(magic),
+                well_known: None,
+                mapper,
+                since: None,
+                min_depth: 0,
+                intersect,
+                retry_policy: None,
+                finalize: None,
+            },
+            utils,
+        )
+        .bootstrap()
+        .unwrap(),
+        shared::CardanoSource::N2C => WithUtils::new(
+            n2c::Config {
+                address,
+                magic: Some(magic),
+                well_known: None,
+                mapper,
+                since: None,
+                min_depth: 0,
+                intersect,
+                retry_policy: None,
+                finalize: None,
+            },
+            utils,
+        )
+        .bootstrap()
+        .unwrap(),
+    };
 
     tracing::info!("{}", "Connection to node established");
 
@@ -83,6 +109,171 @@ fn address_to_bytes(address: &String) -> Vec<u8> {
     }
 }
 
+/// Lovelace and native assets of one output
+fn output_transfers(
+    address: i64,
+    index: i32,
+    output: &TxOutputRecord,
+) -> Vec<super::Transfer<i64>> {
+    let lovelace = super::Transfer {
+        address,
+        asset: super::Asset::default(),
+        output: true,
+        index,
+        amount: output.amount.to_string(),
+        internal: false,
+    };
+    let assets = output.assets.iter().flatten().filter_map(move |a| {
+        Some(super::Transfer {
+            address,
+            asset: super::Asset {
+                kind: shared::AssetKind::CardanoToken,
+                contract: hex::decode(&a.policy).ok()?,
+                name: hex::decode(&a.asset).ok()?,
+            },
+            output: true,
+            index,
+            amount: a.amount.to_string(),
+            internal: false,
+        })
+    });
+    std::iter::once(lovelace).chain(assets).collect()
+}
+
+/// Remove blocks after the rollback point, their transactions are removed
+/// together with them
+async fn rollback(db: &DatabaseConnection, chain_id: i32, slot: u64) -> Result<(), String> {
+    // Addresses stay with their labels, they are only no longer first seen in
+    // the rolled back block
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        UPDATE address SET block_id = NULL
+        WHERE block_id IN (SELECT id FROM block WHERE chain = $1 AND slot > $2);
+        "#,
+        vec![chain_id.into(), (slot as i64).into()],
+    );
+    db.execute(statement).await.map_err(|e| e.to_string())?;
+
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"DELETE FROM block WHERE chain = $1 AND slot > $2;"#,
+        vec![chain_id.into(), (slot as i64).into()],
+    );
+    let result = db.execute(statement).await.map_err(|e| e.to_string())?;
+    tracing::info!("Rolled back blocks: {}", result.rows_affected());
+    Ok(())
+}
+
+/// Store the point where the chain sync continues
+async fn update_cursor(
+    db: &DatabaseConnection,
+    chain: &mut crate::entity::chain::Model,
+    block_hash: String,
+    slot: u64,
+) -> Result<(), String> {
+    let mut params: shared::ChainParam = serde_json::from_value(chain.params.clone()).unwrap();
+
+    if let shared::ChainParam::Cardano(cardano) = &mut params {
+        cardano.block_hash = block_hash;
+        cardano.slot = slot;
+    }
+    let mut chain_change: crate::entity::chain::ActiveModel = chain.clone().into();
+    chain_change.params = Set(serde_json::to_value(params).unwrap());
+
+    *chain = chain_change.update(db).await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Chain sync point just before the block of the given height
+async fn point(
+    db: &DatabaseConnection,
+    chain_id: i32,
+    height: u64,
+) -> Result<(u64, String), String> {
+    if height == 0 {
+        return Err(String::from("Cardano can't rewind before the first block"));
+    }
+
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"SELECT slot, hash FROM block WHERE chain = $1 AND height = $2 AND slot IS NOT NULL;"#,
+        vec![chain_id.into(), (height as i64 - 1).into()],
+    );
+
+    match db.query_one(statement).await {
+        Ok(Some(row)) => {
+            let slot: i64 = row.try_get("", "slot").map_err(|e| e.to_string())?;
+            let hash: Vec<u8> = row.try_get("", "hash").map_err(|e| e.to_string())?;
+            Ok((slot as u64, hex::encode(hash)))
+        }
+        Ok(None) => Err(format!("Cardano block {} is not stored", height - 1)),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Height of the stored block at the chain sync point, the point given in
+/// new params may not be stored
+async fn cursor_height(
+    db: &DatabaseConnection,
+    chain_id: i32,
+    cardano: &shared::Cardano,
+) -> Result<Option<u64>, String> {
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"SELECT height FROM block WHERE chain = $1 AND slot = $2 AND hash = $3;"#,
+        vec![
+            chain_id.into(),
+            (cardano.slot as i64).into(),
+            hex::decode(&cardano.block_hash).unwrap_or_default().into(),
+        ],
+    );
+
+    match db.query_one(statement).await {
+        Ok(Some(row)) => {
+            let height: i64 = row.try_get("", "height").map_err(|e| e.to_string())?;
+            Ok(Some(height as u64))
+        }
+        Ok(None) => Ok(None),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Run oura in its own thread, it is not async
+fn pipeline(
+    cardano: shared::Cardano,
+) -> (
+    tokio::sync::mpsc::Receiver<Event>,
+    tokio::task::JoinHandle<()>,
+) {
+    let (oura_sender, oura_receiver) = tokio::sync::mpsc::channel::<Event>(10);
+    let handle = tokio::task::spawn_blocking(move || {
+        let (source, filter, oura) = oura_bootstrap(&cardano);
+
+        loop {
+            let event = match oura.recv() {
+                Ok(event) => event,
+                Err(_) => {
+                    // Stage died, its panic is the reason for the feed
+                    for stage in [source, filter] {
+                        if let Err(panic) = stage.join() {
+                            std::panic::resume_unwind(panic);
+                        }
+                    }
+                    return;
+                }
+            };
+
+            let forward = matches!(event.data, EventData::Block(_) | EventData::RollBack { .. });
+            // Feed is gone, stop the pipeline
+            if forward && oura_sender.blocking_send(event).is_err() {
+                return;
+            }
+        }
+    });
+    (oura_receiver, handle)
+}
+
 #[async_trait]
 impl super::Feed for shared::Cardano {
     async fn run(
@@ -90,196 +281,206 @@ impl super::Feed for shared::Cardano {
         db: DatabaseConnection,
         mut receiver: tokio::sync::mpsc::Receiver<super::FeedCommand>,
         chain_id: i32,
-    ) {
+    ) -> Result<(), String> {
         tracing::info!("Background job started: Cardano");
         if let Ok(Some(mut chain)) = crate::entity::chain::Entity::find_by_id(chain_id.clone())
             .one(&db)
             .await
         {
-            // let run oura - it is not async :(
-            let (oura_sender, mut oura_receiver) = tokio::sync::mpsc::channel::<BlockRecord>(10);
-            tokio::task::spawn_blocking({
-                let block_hash = self.block_hash.clone();
-                let slot = self.slot.clone();
-                let address = self.address.clone();
-                move || {
-                    let (skip1, skip2, oura) = oura_bootstrap(block_hash, slot, address.clone());
-
-                    loop {
-                        if let Ok(event) = oura.recv() {
-                            match event.data {
-                                EventData::Block(block) => {
-                                    while let Err(_) = oura_sender.try_send(block.clone()) {
-                                        std::thread::sleep(std::time::Duration::from_secs(1));
-                                    }
-                                }
-                                _ => {
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                }
-            });
+            let (mut oura_receiver, mut oura_handle) = pipeline(self.clone());
 
+            // Without the stored point the speed is measured from the first block
+            let height = cursor_height(&db, chain_id, self).await?;
+            let mut measured = height.is_some();
+            let mut status = super::Status::new(chain_id, height.unwrap_or_default());
             loop {
-                // Skip address action
                 tracing::info!("Cardano loop");
-                while let Ok(_) = receiver.try_recv() {}
-
-                if let Some(block) = oura_receiver.recv().await {
-                    if let Some(transactions) = block.transactions {
-                        tracing::info!("Oura recv block: {:?}", block.hash);
-                        let address_list = transactions
-                            .iter()
-                            .map(|t| {
-                                t.outputs
-                                    .iter()
-                                    .flatten()
-                                    .map(|o| address_to_bytes(&o.address))
-                            })
-                            .flatten()
-                            .collect::<Vec<Vec<u8>>>();
-
-                        // Store all known addres to database
-                        self.add_addresses(&db, chain_id, &address_list).await;
-                        let output_address_map =
-                            self.map_address(&db, chain_id, &address_list).await;
-
-                        // Map input addresses
-                        let mut input_address_map: BTreeMap<(Vec<u8>, i64), i64> = BTreeMap::new();
-                        let statement = Statement::from_sql_and_values(
-                            DbBackend::Postgres,
-                            r#"
-                                --- Magic query to transalte hash/index to address id
-
-                                WITH inputs as (SELECT * FROM unnest($1, $2) as x(tx_hash, "index"))
-
-                                SELECT I.tx_hash, index, T."to"[I."index"+1] as address_id FROM inputs I LEFT JOIN transaction T
-                                    ON I.tx_hash = T.hash
-                            "#,
-                            vec![
-                                // Get TX hashes from input
-                                transactions
-                                    .iter()
-                                    .map(|t| {
-                                        t.inputs
-                                            .iter()
-                                            .flatten()
-                                            .map(|i| hex::decode(&i.tx_id).unwrap())
-                                    })
-                                    .flatten()
-                                    .collect::<Vec<Vec<u8>>>()
-                                    .into(),
-                                // Get indexes fron inputs
-                                transactions
-                                    .iter()
-                                    .map(|t| t.inputs.iter().flatten().map(|i| i.index as i64))
-                                    .flatten()
-                                    .collect::<Vec<i64>>()
-                                    .into(),
-                            ],
-                        );
-                        if let Ok(result) = db.query_all(statement).await {
-                            for row in result {
-                                input_address_map.insert(
-                                    (
-                                        row.try_get("", "tx_hash").unwrap(),
-                                        row.try_get("", "index").unwrap(),
-                                    ),
-                                    row.try_get("", "address_id").unwrap_or(0),
-                                );
+
+                let event = tokio::select! {
+                    command = receiver.recv() => {
+                        match command {
+                            Some(super::FeedCommand::Status(reply)) => {
+                                let _ = reply.send(status.report());
+                            }
+                            Some(super::FeedCommand::Pause) => status.paused = true,
+                            Some(super::FeedCommand::Resume) => status.paused = false,
+                            Some(super::FeedCommand::Rewind(height)) => {
+                                match point(&db, chain_id, height).await {
+                                    Ok((slot, block_hash)) => {
+                                        if let Err(err) =
+                                            update_cursor(&db, &mut chain, block_hash.clone(), slot)
+                                                .await
+                                        {
+                                            status.error(err);
+                                            continue;
+                                        }
+                                        self.slot = slot;
+                                        self.block_hash = block_hash;
+                                        // Old pipeline stops once its channel is closed
+                                        (oura_receiver, oura_handle) = pipeline(self.clone());
+                                        status.rewind(height.saturating_sub(1));
+                                    }
+                                    Err(err) => status.error(err),
+                                }
+                            }
+                            Some(super::FeedCommand::Backfill { from, to }) => {
+                                status.error(format!(
+                                    "Cardano can't backfill {}-{}, chain sync only follows the chain, rewind instead",
+                                    from, to
+                                ));
                             }
+                            Some(super::FeedCommand::Stop) | None => break,
                         }
+                        continue;
+                    }
+                    event = oura_receiver.recv(), if !status.paused => event,
+                };
+                let event = match event {
+                    Some(event) => event,
+                    None => {
+                        return match oura_handle.await {
+                            Err(err) => Err(super::supervisor::reason(err)),
+                            Ok(()) => Err(String::from("Oura pipeline stopped")),
+                        };
+                    }
+                };
+                let timestamp = event.context.timestamp.map(|t| t as i64);
 
-                        // Check if transaction exists
-                        if let Ok(query) = crate::entity::transaction::Entity::find()
-                            .select_only()
-                            .column(crate::entity::transaction::Column::Hash)
-                            .filter(
-                                crate::entity::transaction::Column::Hash.is_in(
-                                    transactions
-                                        .iter()
-                                        .map(|t| hex::decode(&t.hash).unwrap())
-                                        .collect::<Vec<Vec<u8>>>(),
-                                ),
+                match event.data {
+                    EventData::Block(block) => {
+                        let block_id = self
+                            .add_block(
+                                &db,
+                                chain_id,
+                                super::Block {
+                                    height: block.number,
+                                    slot: Some(block.slot),
+                                    hash: hex::decode(&block.hash).unwrap(),
+                                    timestamp,
+                                },
                             )
-                            .all(&db)
-                            .await
-                        {
-                            let existing_transaction: BTreeSet<Vec<u8>> =
-                                BTreeSet::from_iter(query.iter().map(|t| t.hash.clone()));
-
-                            // Transactions to add
-                            let transaction_to_insert = transactions
+                            .await;
+
+                        if let Some(transactions) = block.transactions {
+                            tracing::info!("Oura recv block: {:?}", block.hash);
+                            let address_list = transactions
                                 .iter()
-                                .filter(|t| {
-                                    !existing_transaction.contains(&hex::decode(&t.hash).unwrap())
+                                .map(|t| {
+                                    t.outputs
+                                        .iter()
+                                        .flatten()
+                                        .map(|o| address_to_bytes(&o.address))
                                 })
-                                .map(|t| crate::entity::transaction::ActiveModel {
-                                    chain: Set(chain_id.clone()),
-                                    hash: Set(hex::decode(&t.hash).unwrap()),
-                                    from: Set(t
+                                .flatten()
+                                .collect::<Vec<Vec<u8>>>();
+
+                            // Store all known addres to database
+                            // Failed block is not confirmed by the cursor, the feed
+                            // restarts from the last stored one
+                            self.add_addresses(&db, chain_id, &address_list, block_id)
+                                .await?;
+                            self.add_wallets(&db, chain_id, &address_list).await?;
+                            let output_address_map =
+                                self.map_address(&db, chain_id, &address_list).await?;
+
+                            // Map input addresses
+                            let input_address_map = self
+                                .map_inputs(
+                                    &db,
+                                    chain_id,
+                                    &transactions
+                                        .iter()
+                                        .flat_map(|t| {
+                                            t.inputs.iter().flatten().map(|i| {
+                                                (hex::decode(&i.tx_id).unwrap(), i.index as i64)
+                                            })
+                                        })
+                                        .collect::<Vec<(Vec<u8>, i64)>>(),
+                                )
+                                .await;
+
+                            let transaction_list = transactions
+                                .iter()
+                                .enumerate()
+                                .map(|(position, t)| super::UtxoTransaction {
+                                    hash: hex::decode(&t.hash).unwrap(),
+                                    from: t
                                         .inputs
                                         .iter()
                                         .flatten()
-                                        .map(|i| {
-                                            input_address_map.get(&(
-                                                hex::decode(&i.tx_id).unwrap(),
-                                                i.index as i64,
-                                            ))
+                                        .filter_map(|i| {
+                                            input_address_map
+                                                .get(&(
+                                                    hex::decode(&i.tx_id).unwrap(),
+                                                    i.index as i64,
+                                                ))
+                                                .cloned()
                                         })
-                                        .filter(|i| i.is_some() && i.unwrap().gt(&0))
-                                        .map(|i| i.unwrap().clone())
-                                        .collect()),
-                                    to: Set(t
+                                        .collect(),
+                                    to: t
                                         .outputs
                                         .iter()
                                         .flatten()
-                                        .filter(|a| {
+                                        .filter_map(|o| {
                                             output_address_map
-                                                .contains_key(&address_to_bytes(&a.address))
+                                                .get(&address_to_bytes(&o.address))
+                                                .cloned()
                                         })
-                                        .map(|a| {
-                                            output_address_map
-                                                .get(&address_to_bytes(&a.address))
-                                                .unwrap()
-                                                .clone()
+                                        .collect(),
+                                    spent: t
+                                        .inputs
+                                        .iter()
+                                        .flatten()
+                                        .map(|i| (hex::decode(&i.tx_id).unwrap(), i.index as i64))
+                                        .collect(),
+                                    transfers: t
+                                        .outputs
+                                        .iter()
+                                        .flatten()
+                                        .enumerate()
+                                        .filter_map(|(index, o)| {
+                                            Some(output_transfers(
+                                                *output_address_map
+                                                    .get(&address_to_bytes(&o.address))?,
+                                                index as i32,
+                                                o,
+                                            ))
                                         })
-                                        .collect()),
-                                    ..Default::default()
+                                        .flatten()
+                                        .collect(),
+                                    block: block_id,
+                                    timestamp,
+                                    position: Some(position as i32),
                                 })
-                                .collect::<Vec<crate::entity::transaction::ActiveModel>>();
+                                .collect();
 
-                            // If there is no transactions, skip
-                            if transaction_to_insert.len() > 0 {
-                                if let Err(err) = crate::entity::transaction::Entity::insert_many(
-                                    transaction_to_insert,
-                                )
-                                .exec(&db)
-                                .await
-                                {
-                                    tracing::error!("{}", err.to_string());
-                                }
-                            }
+                            self.add_utxo_transactions(&db, chain_id, transaction_list)
+                                .await?;
                         }
-                    }
-
-                    let mut params: shared::ChainParam =
-                        serde_json::from_value(chain.params.clone()).unwrap();
 
-                    if let shared::ChainParam::Cardano(cardano) = &mut params {
-                        cardano.block_hash = block.hash.clone();
-                        cardano.slot = block.slot.clone();
+                        update_cursor(&db, &mut chain, block.hash, block.slot).await?;
+                        if !measured {
+                            status.rewind(block.number.saturating_sub(1));
+                            measured = true;
+                        }
+                        status.success(block.number);
                     }
-                    let mut chain_change: crate::entity::chain::ActiveModel = chain.clone().into();
-                    chain_change.params = Set(serde_json::to_value(params).unwrap());
-
-                    if let Ok(new_chain) = chain_change.update(&db).await {
-                        chain = new_chain;
+                    EventData::RollBack {
+                        block_slot,
+                        block_hash,
+                    } => {
+                        tracing::info!("Oura rollback to: {} {}", block_slot, block_hash);
+                        // Cursor moves only once the blocks are gone, the feed
+                        // restarts from the old one otherwise
+                        rollback(&db, chain_id, block_slot).await?;
+                        update_cursor(&db, &mut chain, block_hash, block_slot).await?;
                     }
+                    _ => {}
                 }
             }
+            Ok(())
+        } else {
+            Err(format!("Chain {} not found", chain_id))
         }
     }
 }
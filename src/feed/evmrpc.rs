@@ -0,0 +1,592 @@
+use crate::feed::Feed;
+use async_trait::async_trait;
+use sea_orm::{
+    ActiveModelTrait, ConnectionTrait, DatabaseConnection, DbBackend, NotSet, Set, Statement,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Call JSON-RPC method on the node
+async fn request(
+    url: &str,
+    method: &str,
+    params: serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": method,
+        "params": params,
+    });
+
+    let response = reqwest::Client::new()
+        .post(url)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let body = response
+        .json::<serde_json::Value>()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if let Some(error) = body.get("error") {
+        return Err(format!("{}: {}", method, error));
+    }
+    Ok(body["result"].clone())
+}
+
+/// Call JSON-RPC method once for each of the params in one batch request,
+/// results are in the order of the params
+async fn batch(
+    url: &str,
+    method: &str,
+    params: Vec<serde_json::Value>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let body = params
+        .into_iter()
+        .enumerate()
+        .map(|(id, params)| {
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": method,
+                "params": params,
+            })
+        })
+        .collect::<Vec<serde_json::Value>>();
+    let size = body.len();
+
+    let response = reqwest::Client::new()
+        .post(url)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let body = response
+        .json::<Vec<serde_json::Value>>()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut results = vec![serde_json::Value::Null; size];
+    for response in body {
+        if let Some(error) = response.get("error") {
+            return Err(format!("{}: {}", method, error));
+        }
+        if let Some(id) = response["id"].as_u64().filter(|id| (*id as usize) < size) {
+            results[id as usize] = response["result"].clone();
+        }
+    }
+    Ok(results)
+}
+
+/// Convert `0x` prefixed hex quantity to number
+fn quantity(value: &serde_json::Value) -> Option<i64> {
+    value
+        .as_str()
+        .and_then(|v| i64::from_str_radix(v.trim_start_matches("0x"), 16).ok())
+}
+
+/// Convert `0x` prefixed hex value to bytes
+fn decode(value: &serde_json::Value) -> Option<Vec<u8>> {
+    value
+        .as_str()
+        .and_then(|v| hex::decode(v.trim_start_matches("0x")).ok())
+}
+
+/// Get receipts for the whole block, fallback to one request per transaction
+async fn receipts(
+    url: &str,
+    number: &str,
+    transactions: &[serde_json::Value],
+) -> Result<BTreeMap<Vec<u8>, serde_json::Value>, String> {
+    let mut map = BTreeMap::new();
+
+    if let Ok(serde_json::Value::Array(receipts)) =
+        request(url, "eth_getBlockReceipts", serde_json::json!([number])).await
+    {
+        for receipt in receipts {
+            if let Some(hash) = decode(&receipt["transactionHash"]) {
+                map.insert(hash, receipt);
+            }
+        }
+        return Ok(map);
+    }
+
+    for transaction in transactions.iter() {
+        let receipt = request(
+            url,
+            "eth_getTransactionReceipt",
+            serde_json::json!([transaction["hash"]]),
+        )
+        .await?;
+        if let Some(hash) = decode(&receipt["transactionHash"]) {
+            map.insert(hash, receipt);
+        }
+    }
+    Ok(map)
+}
+
+fn to_transaction(
+    transactions: &[serde_json::Value],
+    receipts: &BTreeMap<Vec<u8>, serde_json::Value>,
+    block: Option<i64>,
+    timestamp: Option<i64>,
+) -> super::TransactionList {
+    transactions
+        .iter()
+        .filter_map(|t| {
+            let hash = decode(&t["hash"])?;
+            let value = t["value"].as_str().and_then(super::hex_to_decimal);
+            let from: Vec<Vec<u8>> = decode(&t["from"]).into_iter().collect();
+            // Contract creation has no recipient, use created contract instead
+            let to: Vec<Vec<u8>> = decode(&t["to"])
+                .or_else(|| {
+                    receipts
+                        .get(&hash)
+                        .and_then(|r| decode(&r["contractAddress"]))
+                })
+                .into_iter()
+                .collect();
+
+            Some(super::Transaction {
+                hash,
+                transfers: super::native_transfers(&from, &to, value, 0),
+                from,
+                to,
+                block,
+                timestamp,
+                position: quantity(&t["transactionIndex"]).map(|i| i as i32),
+            })
+        })
+        .collect()
+}
+
+/// Native value moved by a contract call inside the transaction
+struct Call {
+    hash: Vec<u8>,
+    /// Trace address like `0_1`, the second subcall of the first call
+    trace: String,
+    from: Vec<u8>,
+    to: Vec<u8>,
+    value: String,
+}
+
+/// Value moving subcalls of the call tracer frame, reverted ones moved nothing
+fn frame_calls(hash: &[u8], frame: &serde_json::Value, trace: &str, calls: &mut Vec<Call>) {
+    for (position, call) in frame["calls"].as_array().into_iter().flatten().enumerate() {
+        let trace = match trace {
+            "" => position.to_string(),
+            _ => format!("{}_{}", trace, position),
+        };
+        if call.get("error").is_some() {
+            continue;
+        }
+        // Delegated calls run in the context of the caller
+        if !matches!(
+            call["type"].as_str(),
+            Some("DELEGATECALL" | "STATICCALL" | "CALLCODE")
+        ) {
+            if let (Some(from), Some(to), Some(value)) = (
+                decode(&call["from"]),
+                decode(&call["to"]),
+                call["value"].as_str().and_then(super::hex_to_decimal),
+            ) {
+                calls.push(Call {
+                    hash: hash.to_vec(),
+                    trace: trace.clone(),
+                    from,
+                    to,
+                    value,
+                });
+            }
+        }
+        frame_calls(hash, call, &trace, calls);
+    }
+}
+
+/// Internal calls of the block transactions from the tracing API of the node
+async fn trace(
+    url: &str,
+    trace: shared::EvmTrace,
+    number: &str,
+    transactions: &[serde_json::Value],
+) -> Result<Vec<Call>, String> {
+    let mut calls = Vec::new();
+    match trace {
+        shared::EvmTrace::None => {}
+        shared::EvmTrace::Debug => {
+            let result = request(
+                url,
+                "debug_traceBlockByNumber",
+                serde_json::json!([number, {"tracer": "callTracer"}]),
+            )
+            .await?;
+            // Older nodes don't return the hash, traces come in the block order
+            for (t, transaction) in result.as_array().into_iter().flatten().zip(transactions) {
+                let hash = decode(&t["txHash"]).or_else(|| decode(&transaction["hash"]));
+                if let Some(hash) = hash.filter(|_| t["result"].get("error").is_none()) {
+                    frame_calls(&hash, &t["result"], "", &mut calls);
+                }
+            }
+        }
+        shared::EvmTrace::Parity => {
+            let result = request(url, "trace_block", serde_json::json!([number])).await?;
+            // Traces are flat, children of a reverted call follow it
+            let mut reverted: Vec<(serde_json::Value, Vec<serde_json::Value>)> = Vec::new();
+            for t in result.as_array().into_iter().flatten() {
+                let address = t["traceAddress"].as_array().cloned().unwrap_or_default();
+                if reverted.iter().any(|(hash, prefix)| {
+                    *hash == t["transactionHash"] && address.starts_with(prefix)
+                }) {
+                    continue;
+                }
+                if t.get("error").is_some() {
+                    reverted.push((t["transactionHash"].clone(), address));
+                    continue;
+                }
+                // Top level trace is the transaction itself
+                if address.is_empty() {
+                    continue;
+                }
+
+                let action = &t["action"];
+                let (from, to, value) = match t["type"].as_str() {
+                    Some("call")
+                        if !matches!(
+                            action["callType"].as_str(),
+                            Some("delegatecall" | "staticcall" | "callcode")
+                        ) =>
+                    {
+                        (&action["from"], &action["to"], &action["value"])
+                    }
+                    Some("create") => (&action["from"], &t["result"]["address"], &action["value"]),
+                    Some("suicide") => (
+                        &action["address"],
+                        &action["refundAddress"],
+                        &action["balance"],
+                    ),
+                    _ => continue,
+                };
+                if let (Some(hash), Some(from), Some(to), Some(value)) = (
+                    decode(&t["transactionHash"]),
+                    decode(from),
+                    decode(to),
+                    value.as_str().and_then(super::hex_to_decimal),
+                ) {
+                    calls.push(Call {
+                        hash,
+                        trace: address
+                            .iter()
+                            .map(|a| a.to_string())
+                            .collect::<Vec<String>>()
+                            .join("_"),
+                        from,
+                        to,
+                        value,
+                    });
+                }
+            }
+        }
+    }
+    Ok(calls)
+}
+
+/// Add internal calls to their transactions, calls are indexed by their trace
+/// address like the internal transactions of the account module
+fn attach_calls(transaction_list: &mut super::TransactionList, calls: Vec<Call>) {
+    let positions: BTreeMap<Vec<u8>, usize> = transaction_list
+        .iter()
+        .enumerate()
+        .map(|(i, t)| (t.hash.clone(), i))
+        .collect();
+
+    for call in calls {
+        if let Some(position) = positions.get(&call.hash) {
+            transaction_list[*position]
+                .transfers
+                .extend(super::internal_transfers(
+                    &call.from,
+                    &call.to,
+                    Some(call.value),
+                    super::trace_index(&call.trace),
+                ));
+        }
+    }
+}
+
+/// Kinds known from the block itself, transactions are signed by keys and
+/// only contracts create contracts or make internal calls
+fn known_kinds(
+    transactions: &[serde_json::Value],
+    receipts: &BTreeMap<Vec<u8>, serde_json::Value>,
+    calls: &[Call],
+) -> BTreeMap<Vec<u8>, shared::AddressKind> {
+    let mut kinds = BTreeMap::new();
+    for transaction in transactions.iter() {
+        if let Some(from) = decode(&transaction["from"]) {
+            kinds.insert(from, shared::AddressKind::Key);
+        }
+    }
+    for receipt in receipts.values() {
+        if let Some(contract) = decode(&receipt["contractAddress"]) {
+            kinds.insert(contract, shared::AddressKind::Contract);
+        }
+    }
+    for call in calls.iter() {
+        kinds.insert(call.from.clone(), shared::AddressKind::Contract);
+    }
+    kinds
+}
+
+/// Stored addresses of the list which are not classified yet
+async fn unclassified(
+    db: &DatabaseConnection,
+    chain_id: i32,
+    address_list: Vec<Vec<u8>>,
+) -> Result<Vec<Vec<u8>>, String> {
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"SELECT hash FROM address WHERE chain = $1 AND hash = ANY($2) AND kind = 0;"#,
+        vec![chain_id.into(), address_list.into()],
+    );
+
+    db.query_all(statement)
+        .await
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|row| row.try_get("", "hash").map_err(|e| e.to_string()))
+        .collect()
+}
+
+/// Classify remaining recipients by their current code. Addresses without
+/// code stay unknown, they may be contracts deployed later or destroyed.
+async fn code_kinds(
+    url: &str,
+    address_list: Vec<Vec<u8>>,
+    kinds: &mut BTreeMap<Vec<u8>, shared::AddressKind>,
+) -> Result<(), String> {
+    if address_list.is_empty() {
+        return Ok(());
+    }
+
+    let params = address_list
+        .iter()
+        .map(|a| serde_json::json!([format!("0x{}", hex::encode(a)), "latest"]))
+        .collect();
+    let codes = batch(url, "eth_getCode", params).await?;
+    for (address, code) in address_list.into_iter().zip(codes) {
+        match code.as_str() {
+            Some("0x") | Some("") | None => {}
+            Some(_) => {
+                kinds.insert(address, shared::AddressKind::Contract);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Hash of the block of the given height known to the node
+async fn block_hash(rpc: &shared::EvmRpc, height: u64) -> Result<Option<Vec<u8>>, String> {
+    let block = request(
+        &rpc.url,
+        "eth_getBlockByNumber",
+        serde_json::json!([format!("{:#0x}", height), false]),
+    )
+    .await?;
+    Ok(decode(&block["hash"]))
+}
+
+/// Store block of the given height if it continues the stored chain
+async fn ingest(
+    rpc: &mut shared::EvmRpc,
+    db: &DatabaseConnection,
+    chain_id: i32,
+    height: u64,
+) -> Result<super::reorg::Ingest, String> {
+    let number = format!("{:#0x}", height);
+    tracing::info!("EvmRpc block: {}", number);
+
+    let block = match request(
+        &rpc.url,
+        "eth_getBlockByNumber",
+        serde_json::json!([number, true]),
+    )
+    .await
+    {
+        Ok(block) if block.is_object() => block,
+        Ok(_) => return Ok(super::reorg::Ingest::Unavailable),
+        Err(err) => return Err(format!("EvmRpc block {}: {}", number, err)),
+    };
+
+    let parent = decode(&block["parentHash"]);
+    if !super::reorg::continues(db, chain_id, height, parent.as_deref()).await? {
+        return Ok(super::reorg::Ingest::Orphaned);
+    }
+
+    let timestamp = quantity(&block["timestamp"]);
+    let block_id = match decode(&block["hash"]) {
+        Some(hash) => {
+            rpc.add_block(
+                db,
+                chain_id,
+                super::Block {
+                    height,
+                    slot: None,
+                    hash,
+                    timestamp,
+                },
+            )
+            .await
+        }
+        None => None,
+    };
+
+    if let Some(transactions) = block["transactions"].as_array() {
+        let receipts = receipts(&rpc.url, &number, transactions)
+            .await
+            .map_err(|e| format!("EvmRpc receipts {}: {}", number, e))?;
+        let mut transaction_list = to_transaction(transactions, &receipts, block_id, timestamp);
+        let logs = receipts
+            .values()
+            .flat_map(|r| r["logs"].as_array().cloned().unwrap_or_default())
+            .collect::<Vec<serde_json::Value>>();
+        super::token::attach(&mut transaction_list, &logs);
+
+        let calls = trace(&rpc.url, rpc.trace, &number, transactions)
+            .await
+            .map_err(|e| format!("EvmRpc trace {}: {}", number, e))?;
+        let mut kinds = known_kinds(transactions, &receipts, &calls);
+        let recipients = transaction_list
+            .iter()
+            .flat_map(|t| t.to.iter())
+            .chain(calls.iter().map(|c| &c.to))
+            .filter(|a| !kinds.contains_key(*a))
+            .cloned()
+            .collect::<BTreeSet<Vec<u8>>>();
+        attach_calls(&mut transaction_list, calls);
+
+        rpc.add_transactions(db, chain_id, transaction_list).await?;
+
+        let recipients = unclassified(db, chain_id, recipients.into_iter().collect()).await?;
+        if let Err(err) = code_kinds(&rpc.url, recipients, &mut kinds).await {
+            tracing::error!("EvmRpc code {}: {}", number, err);
+        }
+        rpc.add_kinds(db, chain_id, kinds).await;
+    }
+    Ok(super::reorg::Ingest::Stored)
+}
+
+/// Store the next block height to chain params
+async fn save(
+    rpc: &shared::EvmRpc,
+    db: &DatabaseConnection,
+    chain: &mut crate::entity::chain::Model,
+) -> Result<(), String> {
+    let mut params: shared::ChainParam = serde_json::from_value(chain.params.clone()).unwrap();
+    if let shared::ChainParam::EvmRpc(evm) = &mut params {
+        evm.last = rpc.last;
+    }
+    let chain_update = crate::entity::chain::ActiveModel {
+        id: Set(chain.id),
+        params: Set(serde_json::to_value(&params).unwrap()),
+        title: NotSet,
+        disabled: NotSet,
+    };
+
+    if chain_update.update(db).await.is_ok() {
+        chain.params = serde_json::to_value(params).unwrap();
+        Ok(())
+    } else {
+        Err(String::from("Chain not updated"))
+    }
+}
+
+#[async_trait]
+impl super::Feed for shared::EvmRpc {
+    async fn wait(&mut self, start: tokio::time::Instant) {
+        let delay = tokio::time::Instant::now().duration_since(start);
+        if let Some(duration) = tokio::time::Duration::from_millis(self.delay).checked_sub(delay) {
+            tracing::info!("EvmRpc sleeping for: {}", duration.as_millis());
+            tokio::time::sleep(duration).await;
+        }
+    }
+
+    // Own node has no request quota, no need to slow down
+    async fn fail(&mut self) {}
+
+    async fn process_block(
+        &mut self,
+        db: &DatabaseConnection,
+        chain: &mut crate::entity::chain::Model,
+    ) -> Result<(), String> {
+        let (height, confirmations) = (self.last, self.confirmations);
+        if !super::reorg::confirmed(self, chain.id, height, confirmations).await? {
+            tracing::info!("EvmRpc block {} not confirmed yet", height);
+            return Ok(());
+        }
+
+        match ingest(self, db, chain.id, height).await? {
+            super::reorg::Ingest::Stored => self.last += 1,
+            super::reorg::Ingest::Unavailable => {
+                tracing::info!("EvmRpc block {} not available yet", height);
+                return Ok(());
+            }
+            super::reorg::Ingest::Orphaned => {
+                let rpc = &*self;
+                let fork = super::reorg::fork(db, chain.id, height, |h| block_hash(rpc, h)).await?;
+                self.last = fork + 1;
+            }
+        }
+        save(self, db, chain).await
+    }
+
+    async fn rewind(
+        &mut self,
+        db: &DatabaseConnection,
+        chain: &mut crate::entity::chain::Model,
+        height: u64,
+    ) -> Result<(), String> {
+        self.last = height;
+        save(self, db, chain).await
+    }
+
+    async fn backfill(
+        &mut self,
+        db: &DatabaseConnection,
+        chain: &mut crate::entity::chain::Model,
+        height: u64,
+    ) -> Result<(), String> {
+        match ingest(self, db, chain.id, height).await? {
+            super::reorg::Ingest::Stored => Ok(()),
+            super::reorg::Ingest::Unavailable => {
+                Err(format!("EvmRpc block {} not available", height))
+            }
+            super::reorg::Ingest::Orphaned => Err(format!(
+                "EvmRpc block {} doesn't continue the stored chain, rewind instead",
+                height
+            )),
+        }
+    }
+
+    fn height(&self) -> u64 {
+        self.last.saturating_sub(1)
+    }
+
+    async fn tip(&mut self) -> Result<Option<u64>, String> {
+        let tip = request(&self.url, "eth_blockNumber", serde_json::json!([])).await?;
+        Ok(tip
+            .as_str()
+            .and_then(|t| u64::from_str_radix(t.trim_start_matches("0x"), 16).ok()))
+    }
+
+    async fn process_address(
+        &mut self,
+        _db: &DatabaseConnection,
+        address: i64,
+    ) -> Result<(), String> {
+        // Plain JSON-RPC has no per address index
+        Err(format!("EvmRpc can't process address {}", address))
+    }
+}
@@ -0,0 +1,84 @@
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, Statement};
+
+/// Attempts before the job fails for good
+pub const ATTEMPTS: i32 = 3;
+
+/// Seconds before the first retry of a failed job, every next one waits 4 times longer
+const RETRY_DELAY: i32 = 60;
+
+/// Mark the oldest queued job of the chain as running, failed jobs wait for
+/// their retry time. Returns job and address IDs
+pub async fn claim(db: &DatabaseConnection, chain_id: i32) -> Option<(i64, i64)> {
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        UPDATE job SET state = 1, attempts = attempts + 1, started = now(), finished = NULL
+        WHERE id = (
+            SELECT id FROM job
+            WHERE chain = $1 AND state = 0 AND (not_before IS NULL OR not_before <= now())
+            ORDER BY id LIMIT 1 FOR UPDATE SKIP LOCKED
+        )
+        RETURNING id, address_id;
+        "#,
+        vec![chain_id.into()],
+    );
+
+    match db.query_one(statement).await {
+        Ok(Some(row)) => Some((
+            row.try_get("", "id").ok()?,
+            row.try_get("", "address_id").ok()?,
+        )),
+        Ok(None) => None,
+        Err(err) => {
+            tracing::error!("Job not claimed: {}", err);
+            None
+        }
+    }
+}
+
+/// Store the job result, failed job is queued again with a growing delay until
+/// it runs out of attempts
+pub async fn finish(db: &DatabaseConnection, job_id: i64, result: Result<(), String>) {
+    let error = result.err();
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        UPDATE job SET
+            state = CASE WHEN $2::text IS NULL THEN 2 WHEN attempts >= $3 THEN 3 ELSE 0 END,
+            error = $2::text,
+            finished = now(),
+            not_before = CASE
+                WHEN $2::text IS NULL OR attempts >= $3 THEN NULL
+                ELSE now() + make_interval(secs => $4 * power(4, attempts - 1))
+            END
+        WHERE id = $1 AND state = 1;
+        "#,
+        vec![
+            job_id.into(),
+            error.into(),
+            ATTEMPTS.into(),
+            RETRY_DELAY.into(),
+        ],
+    );
+
+    if let Err(err) = db.execute(statement).await {
+        tracing::error!("Job {} not finished: {}", job_id, err);
+    }
+}
+
+/// Jobs left running by a stopped or crashed feed are queued again
+pub async fn requeue(db: &DatabaseConnection, chain_id: i32) {
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"UPDATE job SET state = 0 WHERE chain = $1 AND state = 1;"#,
+        vec![chain_id.into()],
+    );
+
+    match db.execute(statement).await {
+        Ok(result) if result.rows_affected() > 0 => {
+            tracing::info!("Requeued jobs: {}", result.rows_affected())
+        }
+        Ok(_) => {}
+        Err(err) => tracing::error!("{}", err.to_string()),
+    }
+}
@@ -1,25 +1,307 @@
 use async_trait::async_trait;
 use sea_orm::{
-    entity::*, query::*, ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, DeriveColumn,
-    EntityTrait, EnumIter, QueryFilter, QuerySelect, Selector, Set, Statement,
+    prelude::DateTimeWithTimeZone, ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait,
+    Set, Statement,
 };
-use std::{
-    collections::{BTreeMap, BTreeSet},
-    ops::Deref,
-};
-use tokio::sync::mpsc::Receiver;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use tokio::sync::mpsc::{error::TryRecvError, Receiver};
 
 mod anyscan;
+mod bitcoin;
 mod cardano;
+mod evmrpc;
+pub mod job;
+mod reorg;
+mod scan;
+pub mod supervisor;
+mod token;
+pub mod wallet;
 
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub enum FeedCommand {
-    Address(i64),
+    /// Report feed progress
+    Status(tokio::sync::oneshot::Sender<shared::FeedStatus>),
+    /// Stop processing new blocks, commands are still handled
+    Pause,
+    Resume,
+    /// Continue from the given height
+    Rewind(u64),
+    /// Process the inclusive range again without moving the cursor
+    Backfill {
+        from: u64,
+        to: u64,
+    },
     Stop,
 }
 
+/// How often the chain tip is refreshed
+const TIP_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_secs(60);
+
+/// Current unix time in seconds
+pub fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Feed progress kept by the run loop
+pub struct Status {
+    chain: i32,
+    height: u64,
+    tip: Option<u64>,
+    tip_time: Option<tokio::time::Instant>,
+    /// Height at the start of the current speed measurement
+    sample: (tokio::time::Instant, u64),
+    blocks_per_second: f64,
+    last_error: Option<String>,
+    last_error_time: Option<i64>,
+    last_success: Option<i64>,
+    pub paused: bool,
+    /// Requested ranges, the first one is in progress
+    pub backfill: VecDeque<shared::Backfill>,
+}
+
+impl Status {
+    pub fn new(chain: i32, height: u64) -> Self {
+        Status {
+            chain,
+            height,
+            tip: None,
+            tip_time: None,
+            sample: (tokio::time::Instant::now(), height),
+            blocks_per_second: 0.0,
+            last_error: None,
+            last_error_time: None,
+            last_success: None,
+            paused: false,
+            backfill: VecDeque::new(),
+        }
+    }
+
+    pub fn success(&mut self, height: u64) {
+        self.height = height;
+        self.last_success = Some(now());
+
+        let elapsed = self.sample.0.elapsed();
+        if elapsed >= TIP_INTERVAL {
+            self.blocks_per_second =
+                height.saturating_sub(self.sample.1) as f64 / elapsed.as_secs_f64();
+            self.sample = (tokio::time::Instant::now(), height);
+        }
+    }
+
+    pub fn error(&mut self, error: String) {
+        tracing::error!("Feed {}: {}", self.chain, error);
+        self.last_error = Some(error);
+        self.last_error_time = Some(now());
+    }
+
+    pub fn tip_outdated(&self) -> bool {
+        self.tip_time.map_or(true, |t| t.elapsed() >= TIP_INTERVAL)
+    }
+
+    pub fn set_tip(&mut self, tip: Option<u64>) {
+        self.tip = tip;
+        self.tip_time = Some(tokio::time::Instant::now());
+    }
+
+    /// Height is set directly, speed measurement starts over
+    pub fn rewind(&mut self, height: u64) {
+        self.height = height;
+        self.sample = (tokio::time::Instant::now(), height);
+    }
+
+    pub fn report(&self) -> shared::FeedStatus {
+        shared::FeedStatus {
+            chain: self.chain,
+            state: if self.paused {
+                shared::FeedState::Paused
+            } else {
+                shared::FeedState::Running
+            },
+            height: self.height,
+            tip: self.tip,
+            blocks_per_second: self.blocks_per_second,
+            last_error: self.last_error.clone(),
+            last_error_time: self.last_error_time,
+            last_success: self.last_success,
+            backfill: self.backfill.front().copied(),
+            ..crash_status(self.chain)
+        }
+    }
+}
+
+/// Status of the feed without a running task, only crashes are known
+pub fn crash_status(chain: i32) -> shared::FeedStatus {
+    let crash = supervisor::crash(chain);
+    shared::FeedStatus {
+        chain,
+        crashes: crash.as_ref().map_or(0, |c| c.count),
+        last_crash: crash.as_ref().map(|c| c.reason.clone()),
+        last_crash_time: crash.map(|c| c.time),
+        ..Default::default()
+    }
+}
+
+/// Block as the feed knows it, timestamp is in unix seconds
+#[derive(Clone, Debug, Default)]
+pub struct Block {
+    pub height: u64,
+    pub slot: Option<u64>,
+    pub hash: Vec<u8>,
+    pub timestamp: Option<i64>,
+}
+
+/// Asset as the feed knows it, native coin has empty contract and name
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Asset {
+    pub kind: shared::AssetKind,
+    pub contract: Vec<u8>,
+    pub name: Vec<u8>,
+}
+
+/// Value moved by one input (`output == false`) or output, amount is a decimal number
+#[derive(Clone, Debug, Default)]
+pub struct Transfer<A> {
+    pub address: A,
+    pub asset: Asset,
+    pub output: bool,
+    pub index: i32,
+    pub amount: String,
+    /// Moved by a contract call inside the transaction
+    pub internal: bool,
+}
+
+/// Transaction with addresses as bytes
+#[derive(Clone, Debug, Default)]
+pub struct Transaction {
+    pub hash: Vec<u8>,
+    pub from: Vec<Vec<u8>>,
+    pub to: Vec<Vec<u8>>,
+    pub transfers: Vec<Transfer<Vec<u8>>>,
+    pub block: Option<i64>,
+    pub timestamp: Option<i64>,
+    pub position: Option<i32>,
+}
+
+/// Transaction with resolved address IDs, outputs are kept in output order
+#[derive(Clone, Debug, Default)]
+pub struct UtxoTransaction {
+    pub hash: Vec<u8>,
+    pub from: Vec<i64>,
+    pub to: Vec<i64>,
+    /// Spent outputs (transaction hash, output index) in input order
+    pub spent: Vec<(Vec<u8>, i64)>,
+    /// Output transfers, input ones are copied from the spent outputs
+    pub transfers: Vec<Transfer<i64>>,
+    pub block: Option<i64>,
+    pub timestamp: Option<i64>,
+    pub position: Option<i32>,
+}
+
 type AddressList = Vec<Vec<u8>>;
-type TransactionList = Vec<(Vec<u8>, Option<u128>, Vec<Vec<u8>>, Vec<Vec<u8>>)>;
+pub type TransactionList = Vec<Transaction>;
+type UtxoTransactionList = Vec<UtxoTransaction>;
+
+/// Convert `0x` prefixed or plain hex number of any size to decimal
+pub fn hex_to_decimal(hex: &str) -> Option<String> {
+    let hex = hex.trim_start_matches("0x");
+    let mut digits: Vec<u8> = vec![0];
+    for c in hex.chars() {
+        // Multiply decimal digits (least significant first) by 16 and add
+        let mut carry = c.to_digit(16)?;
+        for digit in digits.iter_mut() {
+            let value = *digit as u32 * 16 + carry;
+            *digit = (value % 10) as u8;
+            carry = value / 10;
+        }
+        while carry > 0 {
+            digits.push((carry % 10) as u8);
+            carry /= 10;
+        }
+    }
+    while digits.len() > 1 && digits.last() == Some(&0) {
+        digits.pop();
+    }
+    Some(digits.iter().rev().map(|d| (b'0' + d) as char).collect())
+}
+
+/// Convert decimal number of any size to big endian bytes without leading zeros
+pub fn decimal_to_bytes(decimal: &str) -> Option<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in decimal.chars() {
+        // Multiply bytes (least significant first) by 10 and add
+        let mut carry = c.to_digit(10)?;
+        for byte in bytes.iter_mut() {
+            let value = *byte as u32 * 10 + carry;
+            *byte = (value & 0xff) as u8;
+            carry = value >> 8;
+        }
+        while carry > 0 {
+            bytes.push((carry & 0xff) as u8);
+            carry >>= 8;
+        }
+    }
+    bytes.reverse();
+    Some(bytes)
+}
+
+/// Native coin transfer from the sender to the recipient, zero value moves nothing
+pub fn native_transfers(
+    from: &[Vec<u8>],
+    to: &[Vec<u8>],
+    amount: Option<String>,
+    index: i32,
+) -> Vec<Transfer<Vec<u8>>> {
+    match amount {
+        Some(amount) if amount != "0" => from
+            .iter()
+            .map(|a| (a, false))
+            .chain(to.iter().map(|a| (a, true)))
+            .map(|(address, output)| Transfer {
+                address: address.clone(),
+                asset: Asset::default(),
+                output,
+                index,
+                amount: amount.clone(),
+                internal: false,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Transfer index of the internal call at its trace address, `0_1` is the
+/// second subcall of the first call. It doesn't depend on the page or the
+/// crawled address, 0 stays for the native transfer of the transaction.
+pub fn trace_index(trace: &str) -> i32 {
+    let hash = cryptoxide::hashing::keccak256(trace.as_bytes());
+    (i32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]]) & i32::MAX).max(1)
+}
+
+/// Native value moved by a contract call inside the transaction, `index` tells
+/// the calls of one transaction apart
+pub fn internal_transfers(
+    from: &[u8],
+    to: &[u8],
+    amount: Option<String>,
+    index: i32,
+) -> Vec<Transfer<Vec<u8>>> {
+    native_transfers(&[from.to_vec()], &[to.to_vec()], amount, index)
+        .into_iter()
+        .map(|x| Transfer {
+            internal: true,
+            ..x
+        })
+        .collect()
+}
+
+/// Convert unix seconds to database timestamp
+pub fn timestamp(seconds: Option<i64>) -> Option<DateTimeWithTimeZone> {
+    let time = chrono::NaiveDateTime::from_timestamp_opt(seconds?, 0)?;
+    Some(chrono::DateTime::<chrono::Utc>::from_utc(time, chrono::Utc).into())
+}
 
 #[async_trait]
 pub trait Feed {
@@ -28,8 +310,9 @@ pub trait Feed {
         db: DatabaseConnection,
         mut receiver: Receiver<FeedCommand>,
         chain_id: i32,
-    ) {
+    ) -> Result<(), String> {
         tracing::info!("Background job started");
+        let mut status = Status::new(chain_id, self.height());
 
         loop {
             let start = tokio::time::Instant::now();
@@ -39,23 +322,102 @@ pub trait Feed {
                 .await
             {
                 if let Some(mut chain) = result {
-                    if let Ok(msg) = receiver.try_recv() {
+                    let command = receiver.try_recv();
+                    // Feed was removed from the feed channel
+                    if let Err(TryRecvError::Disconnected) = command {
+                        break;
+                    }
+
+                    if let Ok(msg) = command {
                         match msg {
-                            FeedCommand::Address(a) => {
-                                tracing::info!("PROCESSING ADDRESSESSS");
-                                self.process_address(&db, a).await
+                            FeedCommand::Status(reply) => {
+                                let _ = reply.send(status.report());
+                            }
+                            FeedCommand::Pause => status.paused = true,
+                            FeedCommand::Resume => status.paused = false,
+                            FeedCommand::Rewind(height) => {
+                                match self.rewind(&db, &mut chain, height).await {
+                                    Ok(()) => status.rewind(self.height()),
+                                    Err(err) => status.error(err),
+                                }
+                            }
+                            FeedCommand::Backfill { from, to } => {
+                                status.backfill.push_back(shared::Backfill { from, to })
                             }
                             FeedCommand::Stop => break,
                         }
+                    } else if status.paused {
+                        tracing::info!("Feed {} paused", chain_id);
+                    } else if let Some((job, address)) = job::claim(&db, chain_id).await {
+                        tracing::info!("PROCESSING ADDRESSESSS");
+                        let result = self.process_address(&db, address).await;
+                        if let Err(err) = &result {
+                            status.error(err.clone());
+                        }
+                        job::finish(&db, job, result).await;
+                    } else if let Some(range) = status.backfill.front().copied() {
+                        match self.backfill(&db, &mut chain, range.from).await {
+                            Ok(()) if range.from >= range.to => {
+                                status.backfill.pop_front();
+                            }
+                            Ok(()) => status.backfill[0].from += 1,
+                            Err(err) => {
+                                status.error(err);
+                                self.fail().await;
+                            }
+                        }
                     } else {
-                        self.fail().await;
-                        self.process_block(&db, &mut chain).await;
+                        match self.process_block(&db, &mut chain).await {
+                            Ok(()) => status.success(self.height()),
+                            Err(err) => {
+                                status.error(err);
+                                self.fail().await;
+                            }
+                        }
+
+                        if status.tip_outdated() {
+                            match self.tip().await {
+                                Ok(tip) => status.set_tip(tip),
+                                Err(err) => status.error(err),
+                            }
+                        }
                     }
                 }
             }
 
             self.wait(start).await;
         }
+        Ok(())
+    }
+
+    /// Move the cursor, the next processed block is `height`
+    async fn rewind(
+        &mut self,
+        _db: &DatabaseConnection,
+        chain: &mut crate::entity::chain::Model,
+        height: u64,
+    ) -> Result<(), String> {
+        Err(format!("Feed {} can't rewind to {}", chain.id, height))
+    }
+
+    /// Process the block of the given height without moving the cursor
+    async fn backfill(
+        &mut self,
+        _db: &DatabaseConnection,
+        chain: &mut crate::entity::chain::Model,
+        height: u64,
+    ) -> Result<(), String> {
+        Err(format!("Feed {} can't backfill {}", chain.id, height))
+    }
+
+    /// Last processed block height
+    fn height(&self) -> u64 {
+        0
+    }
+
+    /// Height of the newest block known to the source, if the source tells it
+    async fn tip(&mut self) -> Result<Option<u64>, String> {
+        Ok(None)
     }
 
     // Wait to the next cycle
@@ -69,14 +431,93 @@ pub trait Feed {
         tokio::time::sleep(tokio::time::Duration::from_secs(10)).await
     }
 
+    /// Store blocks to the ledger, returns block IDs by block hash
+    async fn add_blocks(
+        &mut self,
+        db: &DatabaseConnection,
+        chain_id: i32,
+        blocks: Vec<Block>,
+    ) -> Result<BTreeMap<Vec<u8>, i64>, String> {
+        let mut map = BTreeMap::new();
+
+        // Same block can't be updated twice by one statement
+        let blocks: BTreeMap<Vec<u8>, Block> =
+            blocks.into_iter().map(|b| (b.hash.clone(), b)).collect();
+        if blocks.is_empty() {
+            return Ok(map);
+        }
+
+        // Arrays can't hold NULL, -1 stands for missing value
+        let statement = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"
+            INSERT INTO
+                block (chain, height, slot, hash, "timestamp")
+                SELECT
+                    $1, T.height, NULLIF(T.slot, -1), T.hash, to_timestamp(NULLIF(T.time, -1))
+                FROM
+                    unnest($2, $3, $4, $5) as T(height, slot, hash, time)
+            ON CONFLICT (chain, hash) DO UPDATE
+                SET height = EXCLUDED.height, "timestamp" = EXCLUDED."timestamp"
+            RETURNING id, hash
+            "#,
+            vec![
+                chain_id.into(),
+                blocks
+                    .values()
+                    .map(|b| b.height as i64)
+                    .collect::<Vec<i64>>()
+                    .into(),
+                blocks
+                    .values()
+                    .map(|b| b.slot.map(|s| s as i64).unwrap_or(-1))
+                    .collect::<Vec<i64>>()
+                    .into(),
+                blocks.keys().cloned().collect::<Vec<Vec<u8>>>().into(),
+                blocks
+                    .values()
+                    .map(|b| b.timestamp.unwrap_or(-1))
+                    .collect::<Vec<i64>>()
+                    .into(),
+            ],
+        );
+
+        for row in db.query_all(statement).await.map_err(|e| e.to_string())? {
+            map.insert(
+                row.try_get("", "hash").map_err(|e| e.to_string())?,
+                row.try_get("", "id").map_err(|e| e.to_string())?,
+            );
+        }
+        Ok(map)
+    }
+
+    /// Store block to the ledger and return its ID
+    async fn add_block(
+        &mut self,
+        db: &DatabaseConnection,
+        chain_id: i32,
+        block: Block,
+    ) -> Option<i64> {
+        let hash = block.hash.clone();
+        match self.add_blocks(db, chain_id, vec![block]).await {
+            Ok(map) => map.get(&hash).cloned(),
+            Err(err) => {
+                tracing::error!("{}", err);
+                None
+            }
+        }
+    }
+
+    /// Store new addresses, `block` is the block where the address was seen first
     async fn add_addresses(
         &mut self,
         db: &DatabaseConnection,
         chain_id: i32,
         address_list: &AddressList,
-    ) {
+        block: Option<i64>,
+    ) -> Result<(), String> {
         if address_list.is_empty() {
-            return;
+            return Ok(());
         }
 
         tracing::info!(
@@ -93,20 +534,142 @@ pub trait Feed {
             WITH new_addresses as (SELECT DISTINCT unnest($2) as hash)
 
             INSERT INTO
-                address (chain, hash)
+                address (chain, hash, block_id)
                 SELECT
-                    $1, T.hash
+                    $1, T.hash, $3
                 FROM
                     new_addresses T
                     LEFT JOIN address A
                         ON A.hash = T.hash AND A.chain = $1
                 WHERE
                     A.id IS NULL
+            RETURNING hash
             "#,
-            vec![chain_id.into(), address_list.clone().into()],
+            vec![chain_id.into(), address_list.clone().into(), block.into()],
         );
 
-        db.execute(statement).await.unwrap();
+        let inserted = db
+            .query_all(statement)
+            .await
+            .map_err(|e| e.to_string())?
+            .iter()
+            .filter_map(|row| row.try_get("", "hash").ok())
+            .collect::<Vec<Vec<u8>>>();
+
+        // Service hashes label addresses as they are stored
+        crate::service::registry::mark(db, chain_id, inserted).await?;
+        Ok(())
+    }
+
+    /// Store credentials of new Shelley addresses and group them into wallets
+    /// by their stake credential, only Cardano chains have them
+    async fn add_wallets(
+        &mut self,
+        db: &DatabaseConnection,
+        chain_id: i32,
+        address_list: &AddressList,
+    ) -> Result<(), String> {
+        let credentials: Vec<(&Vec<u8>, wallet::Credentials)> = address_list
+            .iter()
+            .filter_map(|a| Some((a, wallet::credentials(a)?)))
+            .collect();
+        if credentials.is_empty() {
+            return Ok(());
+        }
+
+        // Arrays can't hold NULL, empty bytes stand for missing credential
+        let stakes = credentials
+            .iter()
+            .map(|(_, c)| c.stake.clone().unwrap_or_default())
+            .collect::<Vec<Vec<u8>>>();
+        let statement = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"
+            INSERT INTO wallet (chain, stake)
+                SELECT DISTINCT $1, S.stake FROM unnest($2::bytea[]) AS S(stake)
+                WHERE S.stake <> ''::bytea
+            ON CONFLICT (chain, stake) DO NOTHING
+            "#,
+            vec![chain_id.into(), stakes.clone().into()],
+        );
+        db.execute(statement).await.map_err(|e| e.to_string())?;
+
+        let statement = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"
+            UPDATE address A
+            SET
+                payment = X.payment, delegation = NULLIF(X.delegation, ''::bytea),
+                wallet_id = W.id, kind = X.kind
+            FROM
+                unnest($2::bytea[], $3::bytea[], $4::bytea[], $5::bytea[], $6::integer[])
+                    AS X(hash, payment, delegation, stake, kind)
+                LEFT JOIN wallet W ON W.chain = $1 AND W.stake = X.stake
+            WHERE A.chain = $1 AND A.hash = X.hash AND A.payment IS NULL
+            "#,
+            vec![
+                chain_id.into(),
+                credentials
+                    .iter()
+                    .map(|(a, _)| (*a).clone())
+                    .collect::<Vec<Vec<u8>>>()
+                    .into(),
+                credentials
+                    .iter()
+                    .map(|(_, c)| c.payment.clone())
+                    .collect::<Vec<Vec<u8>>>()
+                    .into(),
+                credentials
+                    .iter()
+                    .map(|(_, c)| c.delegation.clone().unwrap_or_default())
+                    .collect::<Vec<Vec<u8>>>()
+                    .into(),
+                stakes.into(),
+                credentials
+                    .iter()
+                    .map(|(_, c)| c.kind as i32)
+                    .collect::<Vec<i32>>()
+                    .into(),
+            ],
+        );
+        db.execute(statement).await.map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Classify stored addresses, the first known kind is kept except that a
+    /// contract found later replaces the key, e.g. of a counterfactual wallet
+    async fn add_kinds(
+        &mut self,
+        db: &DatabaseConnection,
+        chain_id: i32,
+        kinds: BTreeMap<Vec<u8>, shared::AddressKind>,
+    ) {
+        if kinds.is_empty() {
+            return;
+        }
+
+        let statement = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"
+            UPDATE address A SET kind = X.kind
+            FROM unnest($2::bytea[], $3::integer[]) AS X(hash, kind)
+            WHERE A.chain = $1 AND A.hash = X.hash AND (A.kind = 0 OR (A.kind = $4 AND X.kind = $5))
+            "#,
+            vec![
+                chain_id.into(),
+                kinds.keys().cloned().collect::<Vec<Vec<u8>>>().into(),
+                kinds
+                    .values()
+                    .map(|k| *k as i32)
+                    .collect::<Vec<i32>>()
+                    .into(),
+                (shared::AddressKind::Key as i32).into(),
+                (shared::AddressKind::Contract as i32).into(),
+            ],
+        );
+        if let Err(err) = db.execute(statement).await {
+            tracing::error!("{}", err.to_string());
+        }
     }
 
     async fn map_address(
@@ -114,7 +677,7 @@ pub trait Feed {
         db: &DatabaseConnection,
         chain_id: i32,
         addresses: &Vec<Vec<u8>>,
-    ) -> BTreeMap<Vec<u8>, i64> {
+    ) -> Result<BTreeMap<Vec<u8>, i64>, String> {
         tracing::info!("Map addresses");
 
         let mut map = BTreeMap::new();
@@ -136,15 +699,42 @@ pub trait Feed {
             vec![chain_id.into(), addresses.clone().into()],
         );
 
-        if let Ok(result) = db.query_all(statement).await {
-            for row in result {
-                map.insert(
-                    row.try_get("", "hash").unwrap(),
-                    row.try_get("", "id").unwrap(),
-                );
-            }
+        for row in db.query_all(statement).await.map_err(|e| e.to_string())? {
+            map.insert(
+                row.try_get("", "hash").map_err(|e| e.to_string())?,
+                row.try_get("", "id").map_err(|e| e.to_string())?,
+            );
         }
-        map
+        Ok(map)
+    }
+
+    /// Stored transactions of the chain with their input and output address IDs
+    async fn map_transactions(
+        &mut self,
+        db: &DatabaseConnection,
+        chain_id: i32,
+        hashes: Vec<Vec<u8>>,
+    ) -> Result<BTreeMap<Vec<u8>, (Vec<i64>, Vec<i64>)>, String> {
+        let mut map = BTreeMap::new();
+        if hashes.is_empty() {
+            return Ok(map);
+        }
+
+        let statement = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"SELECT hash, "from", "to" FROM transaction WHERE chain = $1 AND hash = ANY($2);"#,
+            vec![chain_id.into(), hashes.into()],
+        );
+        for row in db.query_all(statement).await.map_err(|e| e.to_string())? {
+            map.insert(
+                row.try_get("", "hash").map_err(|e| e.to_string())?,
+                (
+                    row.try_get("", "from").map_err(|e| e.to_string())?,
+                    row.try_get("", "to").map_err(|e| e.to_string())?,
+                ),
+            );
+        }
+        Ok(map)
     }
 
     async fn add_transactions(
@@ -152,33 +742,29 @@ pub trait Feed {
         db: &DatabaseConnection,
         chain_id: i32,
         transaction_list: TransactionList,
-    ) {
+    ) -> Result<(), String> {
         if transaction_list.is_empty() {
-            return;
+            return Ok(());
         }
 
-        if let Ok(query) = crate::entity::transaction::Entity::find()
-            .select_only()
-            .column(crate::entity::transaction::Column::Hash)
-            .filter(
-                crate::entity::transaction::Column::Hash.is_in(
-                    transaction_list
-                        .iter()
-                        .map(|t| t.0.clone())
-                        .collect::<Vec<Vec<u8>>>(),
-                ),
+        let transactions = self
+            .map_transactions(
+                db,
+                chain_id,
+                transaction_list.iter().map(|t| t.hash.clone()).collect(),
             )
-            .all(db)
-            .await
+            .await?;
         {
-            let transactions: BTreeSet<Vec<u8>> =
-                BTreeSet::from_iter(query.iter().map(|t| t.hash.clone()));
-
             // Get all address bytes
             let address_list: Vec<Vec<u8>> = BTreeSet::from_iter(
                 transaction_list
                     .iter()
-                    .map(|t| t.2.iter().chain(t.3.iter()))
+                    .map(|t| {
+                        t.from
+                            .iter()
+                            .chain(t.to.iter())
+                            .chain(t.transfers.iter().map(|x| &x.address))
+                    })
                     .flatten()
                     .map(|a| a.clone()),
             )
@@ -186,48 +772,397 @@ pub trait Feed {
             .map(|a| a.clone())
             .collect();
 
-            self.add_addresses(db, chain_id.clone(), &address_list)
-                .await;
+            // Addresses belong to the block only if the whole list comes from it
+            let block = transaction_list
+                .first()
+                .and_then(|t| t.block)
+                .filter(|b| transaction_list.iter().all(|t| t.block == Some(*b)));
+
+            self.add_addresses(db, chain_id.clone(), &address_list, block)
+                .await?;
 
             // Transalte bytes to IDs
-            let address_map = self.map_address(db, chain_id.clone(), &address_list).await;
+            let address_map = self
+                .map_address(db, chain_id.clone(), &address_list)
+                .await?;
 
-            // Insert transactions
-            if let Err(err) = crate::entity::transaction::Entity::insert_many(
-                transaction_list
-                    .iter()
-                    .filter(|t| !transactions.contains(&t.0))
-                    .map(|t| crate::entity::transaction::ActiveModel {
-                        chain: Set(chain_id.clone()),
-                        hash: Set(t.0.clone()),
-                        amount: Set(if let Some(s) = t.1 {
-                            Some(s as u32)
-                        } else {
-                            None
-                        }),
-                        from: Set(BTreeSet::from_iter(
-                            t.2.iter()
-                                .filter(|a| address_map.contains_key(a.deref()))
-                                .map(|a| address_map.get(a).unwrap().clone()),
-                        )
-                        .into_iter()
-                        .collect()),
-                        to: Set(BTreeSet::from_iter(
-                            t.3.iter()
-                                .filter(|a| address_map.contains_key(a.deref()))
-                                .map(|a| address_map.get(a).unwrap().clone()),
-                        )
-                        .into_iter()
-                        .collect()),
-                        ..Default::default()
+            let ids = |addresses: &[Vec<u8>]| -> Vec<i64> {
+                BTreeSet::from_iter(addresses.iter().filter_map(|a| address_map.get(a).cloned()))
+                    .into_iter()
+                    .collect()
+            };
+
+            // Insert transactions, the same hash may come more times in one list
+            let mut seen: BTreeSet<Vec<u8>> = BTreeSet::new();
+            let transaction_to_insert = transaction_list
+                .iter()
+                .filter(|t| !transactions.contains_key(&t.hash) && seen.insert(t.hash.clone()))
+                .map(|t| crate::entity::transaction::ActiveModel {
+                    chain: Set(chain_id.clone()),
+                    hash: Set(t.hash.clone()),
+                    from: Set(ids(&t.from)),
+                    to: Set(ids(&t.to)),
+                    block_id: Set(t.block),
+                    timestamp: Set(timestamp(t.timestamp)),
+                    position: Set(t.position),
+                    ..Default::default()
+                })
+                .collect::<Vec<crate::entity::transaction::ActiveModel>>();
+
+            if !transaction_to_insert.is_empty() {
+                crate::entity::transaction::Entity::insert_many(transaction_to_insert)
+                    .exec(db)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+
+            // Token transfers store the transaction before its sender is known
+            for t in transaction_list.iter().filter(|t| {
+                matches!(transactions.get(&t.hash), Some((from, to)) if from.is_empty() && to.is_empty())
+            }) {
+                let (from, to) = (ids(&t.from), ids(&t.to));
+                if from.is_empty() && to.is_empty() {
+                    continue;
+                }
+                let statement = Statement::from_sql_and_values(
+                    DbBackend::Postgres,
+                    r#"UPDATE transaction SET "from" = $3, "to" = $4 WHERE chain = $1 AND hash = $2;"#,
+                    vec![
+                        chain_id.into(),
+                        t.hash.clone().into(),
+                        from.into(),
+                        to.into(),
+                    ],
+                );
+                db.execute(statement).await.map_err(|e| e.to_string())?;
+            }
+
+            // Transfers are stored for known transactions too, they may bring new ones
+            let transfer_list = transaction_list
+                .iter()
+                .flat_map(|t| {
+                    t.transfers.iter().filter_map(|x| {
+                        Some((
+                            t.hash.clone(),
+                            Transfer {
+                                address: *address_map.get(&x.address)?,
+                                asset: x.asset.clone(),
+                                output: x.output,
+                                index: x.index,
+                                amount: x.amount.clone(),
+                                internal: x.internal,
+                            },
+                        ))
                     })
-                    .collect::<Vec<crate::entity::transaction::ActiveModel>>(),
+                })
+                .collect();
+            self.add_transfers(db, chain_id, transfer_list).await
+        }
+    }
+
+    /// Store assets, returns asset IDs
+    async fn add_assets(
+        &mut self,
+        db: &DatabaseConnection,
+        chain_id: i32,
+        assets: Vec<Asset>,
+    ) -> Result<BTreeMap<Asset, i32>, String> {
+        let mut map = BTreeMap::new();
+
+        let assets: BTreeSet<Asset> = assets.into_iter().collect();
+        if assets.is_empty() {
+            return Ok(map);
+        }
+
+        let statement = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"
+            INSERT INTO
+                asset (chain, kind, contract, name)
+                SELECT $1, T.kind, T.contract, T.name
+                FROM unnest($2::integer[], $3::bytea[], $4::bytea[]) as T(kind, contract, name)
+            ON CONFLICT (chain, contract, name) DO UPDATE SET kind = EXCLUDED.kind
+            RETURNING id, contract, name
+            "#,
+            vec![
+                chain_id.into(),
+                assets
+                    .iter()
+                    .map(|a| a.kind as i32)
+                    .collect::<Vec<i32>>()
+                    .into(),
+                assets
+                    .iter()
+                    .map(|a| a.contract.clone())
+                    .collect::<Vec<Vec<u8>>>()
+                    .into(),
+                assets
+                    .iter()
+                    .map(|a| a.name.clone())
+                    .collect::<Vec<Vec<u8>>>()
+                    .into(),
+            ],
+        );
+
+        let kinds: BTreeMap<(Vec<u8>, Vec<u8>), shared::AssetKind> = assets
+            .iter()
+            .map(|a| ((a.contract.clone(), a.name.clone()), a.kind))
+            .collect();
+        for row in db.query_all(statement).await.map_err(|e| e.to_string())? {
+            let contract: Vec<u8> = row.try_get("", "contract").map_err(|e| e.to_string())?;
+            let name: Vec<u8> = row.try_get("", "name").map_err(|e| e.to_string())?;
+            if let Some(kind) = kinds.get(&(contract.clone(), name.clone())) {
+                map.insert(
+                    Asset {
+                        kind: *kind,
+                        contract,
+                        name,
+                    },
+                    row.try_get("", "id").map_err(|e| e.to_string())?,
+                );
+            }
+        }
+        Ok(map)
+    }
+
+    /// Store transfers of stored transactions identified by hash, known ones are skipped
+    async fn add_transfers(
+        &mut self,
+        db: &DatabaseConnection,
+        chain_id: i32,
+        transfer_list: Vec<(Vec<u8>, Transfer<i64>)>,
+    ) -> Result<(), String> {
+        if transfer_list.is_empty() {
+            return Ok(());
+        }
+
+        let asset_map = self
+            .add_assets(
+                db,
+                chain_id,
+                transfer_list.iter().map(|(_, x)| x.asset.clone()).collect(),
             )
-            .exec(db)
-            .await
-            {
-                tracing::error!("{}", err.to_string());
+            .await?;
+        let transfer_list: Vec<(Vec<u8>, Transfer<i64>, i32)> = transfer_list
+            .into_iter()
+            .filter_map(|(hash, x)| {
+                let asset = *asset_map.get(&x.asset)?;
+                Some((hash, x, asset))
+            })
+            .collect();
+
+        let statement = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"
+            INSERT INTO
+                transfer (transaction_id, address_id, asset_id, output, index, amount, internal)
+                SELECT T.id, X.address, X.asset, X.output, X.index, X.amount::numeric, X.internal
+                FROM
+                    unnest($2::bytea[], $3::bigint[], $4::integer[], $5::boolean[], $6::integer[], $7::text[], $8::boolean[])
+                        as X(hash, address, asset, output, index, amount, internal)
+                    JOIN transaction T ON T.chain = $1 AND T.hash = X.hash
+            ON CONFLICT DO NOTHING
+            "#,
+            vec![
+                chain_id.into(),
+                transfer_list
+                    .iter()
+                    .map(|(hash, _, _)| hash.clone())
+                    .collect::<Vec<Vec<u8>>>()
+                    .into(),
+                transfer_list
+                    .iter()
+                    .map(|(_, x, _)| x.address)
+                    .collect::<Vec<i64>>()
+                    .into(),
+                transfer_list
+                    .iter()
+                    .map(|(_, _, asset)| *asset)
+                    .collect::<Vec<i32>>()
+                    .into(),
+                transfer_list
+                    .iter()
+                    .map(|(_, x, _)| x.output)
+                    .collect::<Vec<bool>>()
+                    .into(),
+                transfer_list
+                    .iter()
+                    .map(|(_, x, _)| x.index)
+                    .collect::<Vec<i32>>()
+                    .into(),
+                transfer_list
+                    .iter()
+                    .map(|(_, x, _)| x.amount.clone())
+                    .collect::<Vec<String>>()
+                    .into(),
+                transfer_list
+                    .iter()
+                    .map(|(_, x, _)| x.internal)
+                    .collect::<Vec<bool>>()
+                    .into(),
+            ],
+        );
+
+        db.execute(statement).await.map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Store input transfers as copies of the spent output transfers
+    async fn spend_outputs(
+        &mut self,
+        db: &DatabaseConnection,
+        chain_id: i32,
+        transaction_list: &[UtxoTransaction],
+    ) -> Result<(), String> {
+        // Spending transaction hash, input index, spent transaction hash and output index
+        let spent: Vec<(Vec<u8>, i32, Vec<u8>, i64)> = transaction_list
+            .iter()
+            .flat_map(|t| {
+                t.spent
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (hash, index))| (t.hash.clone(), i as i32, hash.clone(), *index))
+            })
+            .collect();
+        if spent.is_empty() {
+            return Ok(());
+        }
+
+        let statement = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"
+            INSERT INTO
+                transfer (transaction_id, address_id, asset_id, output, index, amount)
+                SELECT T.id, X.address_id, X.asset_id, false, I.input, X.amount
+                FROM
+                    unnest($2::bytea[], $3::integer[], $4::bytea[], $5::bigint[])
+                        as I(hash, input, spent, output)
+                    JOIN transaction T ON T.chain = $1 AND T.hash = I.hash
+                    JOIN transaction S ON S.chain = $1 AND S.hash = I.spent
+                    JOIN transfer X ON X.transaction_id = S.id AND X.output AND X.index = I.output
+            ON CONFLICT DO NOTHING
+            "#,
+            vec![
+                chain_id.into(),
+                spent
+                    .iter()
+                    .map(|s| s.0.clone())
+                    .collect::<Vec<Vec<u8>>>()
+                    .into(),
+                spent.iter().map(|s| s.1).collect::<Vec<i32>>().into(),
+                spent
+                    .iter()
+                    .map(|s| s.2.clone())
+                    .collect::<Vec<Vec<u8>>>()
+                    .into(),
+                spent.iter().map(|s| s.3).collect::<Vec<i64>>().into(),
+            ],
+        );
+
+        db.execute(statement).await.map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Translate spent outputs (transaction hash, output index) to address IDs
+    async fn map_inputs(
+        &mut self,
+        db: &DatabaseConnection,
+        chain_id: i32,
+        inputs: &[(Vec<u8>, i64)],
+    ) -> BTreeMap<(Vec<u8>, i64), i64> {
+        let mut map = BTreeMap::new();
+        if inputs.is_empty() {
+            return map;
+        }
+
+        let statement = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"
+            --- Magic query to transalte hash/index to address id
+
+            WITH inputs as (SELECT * FROM unnest($1, $2) as x(tx_hash, "index"))
+
+            SELECT I.tx_hash, index, T."to"[I."index"+1] as address_id FROM inputs I LEFT JOIN transaction T
+                ON I.tx_hash = T.hash AND T.chain = $3
+            "#,
+            vec![
+                inputs
+                    .iter()
+                    .map(|i| i.0.clone())
+                    .collect::<Vec<Vec<u8>>>()
+                    .into(),
+                inputs.iter().map(|i| i.1).collect::<Vec<i64>>().into(),
+                chain_id.into(),
+            ],
+        );
+
+        if let Ok(result) = db.query_all(statement).await {
+            for row in result {
+                if let Ok(address_id) = row.try_get::<i64>("", "address_id") {
+                    map.insert(
+                        (
+                            row.try_get("", "tx_hash").unwrap(),
+                            row.try_get("", "index").unwrap(),
+                        ),
+                        address_id,
+                    );
+                }
+            }
+        }
+        map
+    }
+
+    /// Store UTXO transactions, outputs are not deduplicated so later inputs can
+    /// be resolved by the output index
+    async fn add_utxo_transactions(
+        &mut self,
+        db: &DatabaseConnection,
+        chain_id: i32,
+        transaction_list: UtxoTransactionList,
+    ) -> Result<(), String> {
+        if transaction_list.is_empty() {
+            return Ok(());
+        }
+
+        // Check if transaction exists
+        let existing_transaction = self
+            .map_transactions(
+                db,
+                chain_id,
+                transaction_list.iter().map(|t| t.hash.clone()).collect(),
+            )
+            .await?;
+        {
+            let transaction_to_insert = transaction_list
+                .iter()
+                .filter(|t| !existing_transaction.contains_key(&t.hash))
+                .map(|t| crate::entity::transaction::ActiveModel {
+                    chain: Set(chain_id),
+                    hash: Set(t.hash.clone()),
+                    from: Set(t.from.clone()),
+                    to: Set(t.to.clone()),
+                    block_id: Set(t.block),
+                    timestamp: Set(timestamp(t.timestamp)),
+                    position: Set(t.position),
+                    ..Default::default()
+                })
+                .collect::<Vec<crate::entity::transaction::ActiveModel>>();
+
+            // If there is no transactions, skip
+            if !transaction_to_insert.is_empty() {
+                crate::entity::transaction::Entity::insert_many(transaction_to_insert)
+                    .exec(db)
+                    .await
+                    .map_err(|e| e.to_string())?;
             }
+
+            // Outputs go first, inputs may spend outputs of the same list
+            let transfer_list = transaction_list
+                .iter()
+                .flat_map(|t| t.transfers.iter().map(|x| (t.hash.clone(), x.clone())))
+                .collect();
+            self.add_transfers(db, chain_id, transfer_list).await?;
+            self.spend_outputs(db, chain_id, &transaction_list).await
         }
     }
 
@@ -235,13 +1170,17 @@ pub trait Feed {
         &mut self,
         db: &DatabaseConnection,
         chain: &mut crate::entity::chain::Model,
-    ) {
+    ) -> Result<(), String> {
         tokio::time::sleep(tokio::time::Duration::from_millis(5_000)).await;
         tracing::info!("Processing block for empty");
+        Ok(())
     }
 
-    async fn process_address(&mut self, db: &DatabaseConnection, address: i64) {
-        tokio::time::sleep(tokio::time::Duration::from_millis(5_000)).await;
-        tracing::info!("Processing address fo empty");
+    async fn process_address(
+        &mut self,
+        db: &DatabaseConnection,
+        address: i64,
+    ) -> Result<(), String> {
+        Err(format!("Feed can't process address {}", address))
     }
 }
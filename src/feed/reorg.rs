@@ -0,0 +1,173 @@
+//! Chain reorganization handling of EVM feeds
+
+use super::Feed;
+use once_cell::sync::Lazy;
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, Statement};
+use std::{collections::HashMap, future::Future, sync::Mutex};
+
+/// Deepest reorganization resolved automatically, deeper one needs a rewind
+const MAX_DEPTH: u64 = 256;
+
+/// Result of the block ingest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ingest {
+    Stored,
+    /// Source doesn't have the block yet
+    Unavailable,
+    /// Parent hash doesn't match the stored block, nothing was stored
+    Orphaned,
+}
+
+/// Newest tip seen by each chain feed
+fn tips() -> &'static Mutex<HashMap<i32, u64>> {
+    static TIPS: Lazy<Mutex<HashMap<i32, u64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+    &TIPS
+}
+
+/// Block is confirmed once the tip is `confirmations` blocks ahead of it, the
+/// tip is requested only when the last known one is not enough
+pub async fn confirmed<F: Feed + Send>(
+    feed: &mut F,
+    chain_id: i32,
+    height: u64,
+    confirmations: u64,
+) -> Result<bool, String> {
+    if confirmations == 0 {
+        return Ok(true);
+    }
+
+    let known = tips().lock().unwrap().get(&chain_id).cloned();
+    if known.map_or(false, |tip| tip >= height + confirmations) {
+        return Ok(true);
+    }
+
+    match feed.tip().await? {
+        Some(tip) => {
+            tips().lock().unwrap().insert(chain_id, tip);
+            Ok(tip >= height + confirmations)
+        }
+        None => Ok(true),
+    }
+}
+
+/// Hashes of the stored blocks of the height, orphaned ones may be there too
+async fn stored(
+    db: &DatabaseConnection,
+    chain_id: i32,
+    height: u64,
+) -> Result<Vec<Vec<u8>>, String> {
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"SELECT hash FROM block WHERE chain = $1 AND height = $2;"#,
+        vec![chain_id.into(), (height as i64).into()],
+    );
+
+    db.query_all(statement)
+        .await
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|row| row.try_get("", "hash").map_err(|e| e.to_string()))
+        .collect()
+}
+
+/// Block continues the stored chain, unless its parent was not stored at all
+pub async fn continues(
+    db: &DatabaseConnection,
+    chain_id: i32,
+    height: u64,
+    parent: Option<&[u8]>,
+) -> Result<bool, String> {
+    let (Some(height), Some(parent)) = (height.checked_sub(1), parent) else {
+        return Ok(true);
+    };
+    let stored = stored(db, chain_id, height).await?;
+    Ok(stored.is_empty() || stored.iter().any(|h| h == parent))
+}
+
+/// Remove blocks the feed stored after the fork up to the orphaned `height`
+/// and other blocks of the fork height, their transactions and transfers are
+/// removed with them. Address crawls may store canonical blocks ahead of the
+/// feed, those stay.
+async fn remove(
+    db: &DatabaseConnection,
+    chain_id: i32,
+    fork: u64,
+    hash: &[u8],
+    height: u64,
+) -> Result<u64, String> {
+    // Addresses stay, they are only no longer first seen in the orphaned block
+    let orphaned = r#"
+        SELECT id FROM block
+        WHERE chain = $1 AND (
+            (height > $2 AND height < $4) OR (height = $2 AND hash <> $3)
+        )
+    "#;
+    let values = || {
+        vec![
+            chain_id.into(),
+            (fork as i64).into(),
+            hash.to_vec().into(),
+            (height as i64).into(),
+        ]
+    };
+
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        &format!(
+            "UPDATE address SET block_id = NULL WHERE block_id IN ({});",
+            orphaned
+        ),
+        values(),
+    );
+    db.execute(statement).await.map_err(|e| e.to_string())?;
+
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        &format!("DELETE FROM block WHERE id IN ({});", orphaned),
+        values(),
+    );
+    db.execute(statement)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(|e| e.to_string())
+}
+
+/// Find the newest stored block the source still has, walking down from the
+/// parent of the orphaned block, and remove the blocks stored after it.
+/// Returns the fork height, the feed continues with the next one.
+pub async fn fork<F, Fut>(
+    db: &DatabaseConnection,
+    chain_id: i32,
+    height: u64,
+    mut hash: F,
+) -> Result<u64, String>
+where
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = Result<Option<Vec<u8>>, String>>,
+{
+    let mut fork = height.saturating_sub(1);
+    loop {
+        let source = hash(fork)
+            .await?
+            .ok_or_else(|| format!("Block {} not available", fork))?;
+        let stored = stored(db, chain_id, fork).await?;
+
+        if stored.is_empty() || stored.contains(&source) {
+            let removed = remove(db, chain_id, fork, &source, height).await?;
+            tracing::info!(
+                "Chain {} reorganized at {}: {} orphaned blocks removed",
+                chain_id,
+                fork,
+                removed
+            );
+            return Ok(fork);
+        }
+        if fork == 0 || height - fork >= MAX_DEPTH {
+            return Err(format!(
+                "Chain {} reorganized deeper than {} blocks below {}",
+                chain_id, MAX_DEPTH, height
+            ));
+        }
+        fork -= 1;
+    }
+}
@@ -0,0 +1,126 @@
+//! Client for Etherscan compatible APIs (Etherscan, Polygonscan, Arbiscan...)
+
+use once_cell::sync::Lazy;
+use std::{collections::HashMap, sync::Mutex};
+use tokio::time::{Duration, Instant};
+
+/// Most results the account module returns for one request
+pub const PAGE_SIZE: usize = 1000;
+
+/// Attempts before the request is reported as failed
+const ATTEMPTS: u32 = 6;
+
+/// Backoff after the first failure, doubled with every next one
+const BACKOFF: Duration = Duration::from_secs(1);
+
+/// Time when each API key may be used next, shared by all feeds using the key
+fn schedule() -> &'static Mutex<HashMap<String, Instant>> {
+    static SCHEDULE: Lazy<Mutex<HashMap<String, Instant>>> =
+        Lazy::new(|| Mutex::new(HashMap::new()));
+    &SCHEDULE
+}
+
+/// All configured API keys, some explorers work without a key too
+fn keys(any: &shared::AnyScan) -> Vec<String> {
+    let keys: Vec<String> = std::iter::once(&any.token)
+        .chain(any.tokens.iter())
+        .filter(|k| !k.is_empty())
+        .cloned()
+        .collect();
+    if keys.is_empty() {
+        vec![String::new()]
+    } else {
+        keys
+    }
+}
+
+/// Take the key which is available first and reserve its next slot
+async fn reserve(
+    any: &shared::AnyScan,
+    keys: &[String],
+    penalty: Option<(&str, Duration)>,
+) -> String {
+    let interval = Duration::from_millis(any.delay);
+    let (key, slot) = {
+        let mut schedule = schedule().lock().unwrap();
+        let now = Instant::now();
+
+        if let Some((key, backoff)) = penalty {
+            schedule.insert(key.to_string(), now + backoff);
+        }
+
+        let (key, slot) = keys
+            .iter()
+            .map(|k| (k, schedule.get(k).cloned().unwrap_or(now).max(now)))
+            .min_by_key(|(_, slot)| *slot)
+            .unwrap();
+        schedule.insert(key.clone(), slot + interval);
+        (key.clone(), slot)
+    };
+
+    tokio::time::sleep_until(slot).await;
+    key
+}
+
+/// Call the API and return the `result` field, `query` is without the API key
+pub async fn request(any: &shared::AnyScan, query: &str) -> Result<serde_json::Value, String> {
+    let keys = keys(any);
+    let mut penalty: Option<(String, Duration)> = None;
+    let mut error = String::new();
+
+    for attempt in 0..ATTEMPTS {
+        let key = reserve(any, &keys, penalty.as_ref().map(|(k, d)| (k.as_str(), *d))).await;
+        let backoff = BACKOFF * 2u32.pow(attempt);
+
+        let url = format!("{}?{}&apikey={}", any.base_url, query, key);
+        tracing::info!("AnyScan request: {}?{}", any.base_url, query);
+
+        let response = match reqwest::get(&url).await {
+            Ok(response) => response,
+            Err(err) => {
+                error = err.to_string();
+                penalty = Some((key, backoff));
+                continue;
+            }
+        };
+
+        let status = response.status();
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+            error = format!("HTTP {}", status);
+            penalty = Some((key, backoff));
+            continue;
+        }
+
+        let body = match response.json::<serde_json::Value>().await {
+            Ok(body) => body,
+            Err(err) => {
+                error = err.to_string();
+                penalty = Some((key, backoff));
+                continue;
+            }
+        };
+
+        // Proxy module answers in JSON-RPC format
+        if let Some(err) = body.get("error") {
+            return Err(format!("{}: {}", query, err));
+        }
+
+        // Account and logs modules report problems with status 0
+        if body["status"].as_str() == Some("0") {
+            let message = body["message"].as_str().unwrap_or_default();
+            if message.starts_with("No ") {
+                return Ok(serde_json::Value::Array(Vec::new()));
+            }
+            // Rate limit (`NOTOK`), invalid key or temporary failure, another key may help
+            error = format!("{}: {}", message, body["result"]);
+            penalty = Some((key, backoff));
+            continue;
+        }
+
+        return Ok(body["result"].clone());
+    }
+
+    let error = format!("{} failed after {} attempts: {}", query, ATTEMPTS, error);
+    tracing::error!("AnyScan: {}", error);
+    Err(error)
+}
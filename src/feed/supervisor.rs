@@ -0,0 +1,250 @@
+use super::Feed;
+use once_cell::sync::Lazy;
+use sea_orm::{DatabaseConnection, EntityTrait};
+use std::{collections::HashMap, sync::Mutex};
+use tokio::sync::mpsc::{Receiver, WeakSender};
+use tokio::time::{Duration, Instant};
+
+/// Delay before the first restart, it doubles with every crash
+const RESTART_DELAY: Duration = Duration::from_secs(5);
+
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(300);
+
+/// Feed running at least this long starts with the shortest delay again
+const STABLE: Duration = Duration::from_secs(600);
+
+/// Crashes of one chain feed since the start of the process
+#[derive(Clone, Debug, Default)]
+pub struct Crash {
+    pub count: u32,
+    pub reason: String,
+    pub time: i64,
+}
+
+fn crashes() -> &'static Mutex<HashMap<i32, Crash>> {
+    static CRASHES: Lazy<Mutex<HashMap<i32, Crash>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+    &CRASHES
+}
+
+pub fn crash(chain_id: i32) -> Option<Crash> {
+    crashes().lock().unwrap().get(&chain_id).cloned()
+}
+
+fn record(chain_id: i32, reason: String) {
+    tracing::error!("Feed {} crashed: {}", chain_id, reason);
+    let mut crashes = crashes().lock().unwrap();
+    let crash = crashes.entry(chain_id).or_default();
+    crash.count += 1;
+    crash.reason = reason;
+    crash.time = super::now();
+}
+
+/// Panic message of the task, if it panicked
+pub fn reason(err: tokio::task::JoinError) -> String {
+    if !err.is_panic() {
+        return err.to_string();
+    }
+
+    let panic = err.into_panic();
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("Unknown panic")
+    }
+}
+
+/// Start feed task of the chain params, chain without params has no feed
+fn spawn(
+    params: shared::ChainParam,
+    db: DatabaseConnection,
+    receiver: Receiver<super::FeedCommand>,
+    chain_id: i32,
+) -> Option<tokio::task::JoinHandle<Result<(), String>>> {
+    match params {
+        shared::ChainParam::ArbiScan(mut anyscan)
+        | shared::ChainParam::EtherScan(mut anyscan)
+        | shared::ChainParam::PolyScan(mut anyscan) => Some(tokio::task::spawn(async move {
+            anyscan.run(db, receiver, chain_id).await
+        })),
+        shared::ChainParam::EvmRpc(mut rpc) => Some(tokio::task::spawn(async move {
+            rpc.run(db, receiver, chain_id).await
+        })),
+        shared::ChainParam::Bitcoin(mut bitcoin) => Some(tokio::task::spawn(async move {
+            bitcoin.run(db, receiver, chain_id).await
+        })),
+        shared::ChainParam::Cardano(mut cardano) => Some(tokio::task::spawn(async move {
+            cardano.run(db, receiver, chain_id).await
+        })),
+        shared::ChainParam::None => None,
+    }
+}
+
+/// Longest wait for the node or API to answer the probe
+const PROBE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Node of the Cardano params accepts connections, `host:port` is reached by
+/// TCP and anything else is a Unix socket
+async fn connect(cardano: &shared::Cardano) -> Result<(), String> {
+    if cardano.address.contains(':') {
+        tokio::net::TcpStream::connect(&cardano.address)
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("{}: {}", cardano.address, e))
+    } else {
+        tokio::net::UnixStream::connect(&cardano.address)
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("{}: {}", cardano.address, e))
+    }
+}
+
+/// Check the chain params before they are stored, the source must answer
+/// with its tip so the feed can start from them
+pub async fn probe(params: &shared::ChainParam) -> Result<(), String> {
+    let tip = match params.clone() {
+        shared::ChainParam::ArbiScan(mut anyscan)
+        | shared::ChainParam::EtherScan(mut anyscan)
+        | shared::ChainParam::PolyScan(mut anyscan) => {
+            if anyscan.base_url.is_empty() {
+                return Err(String::from("Missing base URL"));
+            }
+            tokio::time::timeout(PROBE_TIMEOUT, anyscan.tip()).await
+        }
+        shared::ChainParam::EvmRpc(mut rpc) => {
+            if rpc.url.is_empty() {
+                return Err(String::from("Missing node URL"));
+            }
+            tokio::time::timeout(PROBE_TIMEOUT, rpc.tip()).await
+        }
+        shared::ChainParam::Bitcoin(mut bitcoin) => {
+            if bitcoin.url.is_empty() {
+                return Err(String::from("Missing node URL"));
+            }
+            tokio::time::timeout(PROBE_TIMEOUT, bitcoin.tip()).await
+        }
+        shared::ChainParam::Cardano(cardano) => {
+            // Chain sync starts from the intersection point
+            if hex::decode(&cardano.block_hash).map(|h| h.len()) != Ok(32) {
+                return Err(format!("Invalid block hash: {}", cardano.block_hash));
+            }
+            return match tokio::time::timeout(PROBE_TIMEOUT, connect(&cardano)).await {
+                Ok(result) => result,
+                Err(_) => Err(format!("{}: no answer", cardano.address)),
+            };
+        }
+        shared::ChainParam::None => return Ok(()),
+    };
+
+    match tip {
+        Ok(Ok(Some(_))) => Ok(()),
+        Ok(Ok(None)) => Err(String::from("Source doesn't tell its tip")),
+        Ok(Err(err)) => Err(err),
+        Err(_) => Err(String::from("Source doesn't answer")),
+    }
+}
+
+/// Usual time for a feed to finish its block and stop
+pub const STOP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Stop the running feed of the chain and wait until its task has finished,
+/// so it can't store its progress over new params. Busy feed may take long,
+/// callers decide how long to wait.
+pub async fn stop(feed_channel: &crate::FeedChannel, chain_id: i32) {
+    let sender = match feed_channel.write().await.remove(&chain_id) {
+        Some(sender) => sender,
+        None => return,
+    };
+
+    // Feed answers between blocks, the receiver is dropped once it returns
+    let _ = sender.send(super::FeedCommand::Stop).await;
+    sender.closed().await;
+}
+
+/// Remove the feed sender unless it was already replaced by another feed
+async fn release(
+    feed_channel: &crate::FeedChannel,
+    chain_id: i32,
+    sender: &WeakSender<super::FeedCommand>,
+) {
+    let mut feed_channel = feed_channel.write().await;
+    if let (Some(current), Some(sender)) = (feed_channel.get(&chain_id), sender.upgrade()) {
+        if current.same_channel(&sender) {
+            feed_channel.remove(&chain_id);
+        }
+    }
+}
+
+/// Run the chain feed and restart it after a crash, until it is stopped or
+/// its sender is removed from the feed channel
+pub async fn supervise(
+    db: DatabaseConnection,
+    feed_channel: crate::FeedChannel,
+    chain_id: i32,
+    mut sender: WeakSender<super::FeedCommand>,
+    mut receiver: Receiver<super::FeedCommand>,
+) {
+    let mut delay = RESTART_DELAY;
+
+    loop {
+        // Feed stores its progress to params, restarted one continues from there
+        let params = match crate::entity::chain::Entity::find_by_id(chain_id)
+            .one(&db)
+            .await
+        {
+            Ok(Some(chain)) => serde_json::from_value(chain.params).unwrap_or_default(),
+            Ok(None) => shared::ChainParam::None,
+            Err(err) => {
+                tracing::error!("Feed {} not started: {}", chain_id, err);
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+        };
+
+        super::job::requeue(&db, chain_id).await;
+        let started = Instant::now();
+        let handle = match spawn(params, db.clone(), receiver, chain_id) {
+            Some(handle) => handle,
+            None => {
+                release(&feed_channel, chain_id, &sender).await;
+                return;
+            }
+        };
+
+        let reason = match handle.await {
+            Ok(Ok(())) => {
+                tracing::info!("Feed {} stopped", chain_id);
+                release(&feed_channel, chain_id, &sender).await;
+                return;
+            }
+            Ok(Err(reason)) => reason,
+            Err(err) => reason(err),
+        };
+        record(chain_id, reason);
+
+        if started.elapsed() >= STABLE {
+            delay = RESTART_DELAY;
+        }
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RESTART_DELAY);
+
+        // Commands sent meanwhile were lost together with the old receiver
+        let (new_sender, new_receiver) = tokio::sync::mpsc::channel(16);
+        {
+            let mut feed_channel = feed_channel.write().await;
+            match (feed_channel.get(&chain_id), sender.upgrade()) {
+                (Some(current), Some(old)) if current.same_channel(&old) => {
+                    feed_channel.insert(chain_id, new_sender.clone());
+                }
+                _ => {
+                    tracing::info!("Feed {} was removed, not restarted", chain_id);
+                    return;
+                }
+            }
+        }
+        tracing::info!("Feed {} restarted", chain_id);
+        sender = new_sender.downgrade();
+        receiver = new_receiver;
+    }
+}
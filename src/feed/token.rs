@@ -0,0 +1,317 @@
+//! ERC-20, ERC-721 and ERC-1155 transfer log decoding
+
+use super::{hex_to_decimal, Asset, Transaction, Transfer};
+use std::collections::BTreeMap;
+
+/// `Transfer(address,address,uint256)`, ERC-721 has the token id indexed
+pub const TRANSFER: &str = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+/// `TransferSingle(address,address,address,uint256,uint256)`
+pub const TRANSFER_SINGLE: &str =
+    "0xc3d58168c5ae7397731d063d5bbf3d657854427343f4c083240f7aacaa2d0f62";
+/// `TransferBatch(address,address,address,uint256[],uint256[])`
+pub const TRANSFER_BATCH: &str =
+    "0x4a39dc06d4c0dbc64b70af90fd698a233a518aa5d07e595d983b8c0526c8f7fb";
+
+/// Convert `0x` prefixed hex value to bytes
+fn decode(value: &serde_json::Value) -> Option<Vec<u8>> {
+    value
+        .as_str()
+        .and_then(|v| hex::decode(v.trim_start_matches("0x")).ok())
+}
+
+/// Address is stored in the last 20 bytes of the word
+fn address(word: &[u8]) -> Option<Vec<u8>> {
+    Some(word.get(word.len().checked_sub(20)?..)?.to_vec())
+}
+
+/// Token id without leading zeros
+fn token_id(word: &[u8]) -> Vec<u8> {
+    word.iter().cloned().skip_while(|b| *b == 0).collect()
+}
+
+fn amount(word: &[u8]) -> Option<String> {
+    hex_to_decimal(&hex::encode(word))
+}
+
+/// 32 bytes word of ABI encoded data
+fn word(data: &[u8], index: usize) -> Option<&[u8]> {
+    data.get(index * 32..(index + 1) * 32)
+}
+
+/// Dynamic `uint256[]` stored at the offset given by the word at `index`
+fn array(data: &[u8], index: usize) -> Option<Vec<&[u8]>> {
+    let offset = u64::from_str_radix(&hex::encode(word(data, index)?), 16).ok()? as usize / 32;
+    let length = u64::from_str_radix(&hex::encode(word(data, offset)?), 16).ok()? as usize;
+    (0..length).map(|i| word(data, offset + 1 + i)).collect()
+}
+
+/// Sender and recipient sides of one token movement
+pub fn pair(
+    from: Vec<u8>,
+    to: Vec<u8>,
+    asset: Asset,
+    index: i32,
+    amount: String,
+) -> Vec<Transfer<Vec<u8>>> {
+    vec![
+        Transfer {
+            address: from,
+            asset: asset.clone(),
+            output: false,
+            index,
+            amount: amount.clone(),
+            internal: false,
+        },
+        Transfer {
+            address: to,
+            asset,
+            output: true,
+            index,
+            amount,
+            internal: false,
+        },
+    ]
+}
+
+/// Decode transfers from one log, unknown logs give nothing
+pub fn transfers(log: &serde_json::Value) -> Vec<Transfer<Vec<u8>>> {
+    let decoded = || -> Option<Vec<Transfer<Vec<u8>>>> {
+        let contract = decode(&log["address"])?;
+        let topics = log["topics"]
+            .as_array()?
+            .iter()
+            .map(decode)
+            .collect::<Option<Vec<Vec<u8>>>>()?;
+        let data = decode(&log["data"]).unwrap_or_default();
+        // Node returns `0x` for zero log index
+        let index = i64::from_str_radix(log["logIndex"].as_str()?.trim_start_matches("0x"), 16)
+            .unwrap_or(0) as i32;
+
+        match hex::encode(topics.first()?).as_str() {
+            t if t == &TRANSFER[2..] && topics.len() == 3 => Some(pair(
+                address(&topics[1])?,
+                address(&topics[2])?,
+                Asset {
+                    kind: shared::AssetKind::Erc20,
+                    contract,
+                    name: Vec::new(),
+                },
+                index,
+                amount(word(&data, 0)?)?,
+            )),
+            t if t == &TRANSFER[2..] && topics.len() == 4 => Some(pair(
+                address(&topics[1])?,
+                address(&topics[2])?,
+                Asset {
+                    kind: shared::AssetKind::Erc721,
+                    contract,
+                    name: token_id(&topics[3]),
+                },
+                index,
+                "1".to_string(),
+            )),
+            t if t == &TRANSFER_SINGLE[2..] && topics.len() == 4 => Some(pair(
+                address(&topics[2])?,
+                address(&topics[3])?,
+                Asset {
+                    kind: shared::AssetKind::Erc1155,
+                    contract,
+                    name: token_id(word(&data, 0)?),
+                },
+                index,
+                amount(word(&data, 1)?)?,
+            )),
+            t if t == &TRANSFER_BATCH[2..] && topics.len() == 4 => {
+                let (from, to) = (address(&topics[2])?, address(&topics[3])?);
+                let ids = array(&data, 0)?;
+                let values = array(&data, 1)?;
+                if ids.len() != values.len() {
+                    return None;
+                }
+                ids.iter()
+                    .zip(values.iter())
+                    .map(|(id, value)| {
+                        Some(pair(
+                            from.clone(),
+                            to.clone(),
+                            Asset {
+                                kind: shared::AssetKind::Erc1155,
+                                contract: contract.clone(),
+                                name: token_id(id),
+                            },
+                            index,
+                            amount(value)?,
+                        ))
+                    })
+                    .collect::<Option<Vec<Vec<Transfer<Vec<u8>>>>>>()
+                    .map(|t| t.into_iter().flatten().collect())
+            }
+            _ => None,
+        }
+    };
+    decoded().unwrap_or_default()
+}
+
+/// Attach token transfers from logs to transactions of the list
+pub fn attach(transaction_list: &mut [Transaction], logs: &[serde_json::Value]) {
+    let mut log_map: BTreeMap<Vec<u8>, Vec<Transfer<Vec<u8>>>> = BTreeMap::new();
+    for log in logs {
+        if let Some(hash) = decode(&log["transactionHash"]) {
+            log_map.entry(hash).or_default().extend(transfers(log));
+        }
+    }
+    for transaction in transaction_list.iter_mut() {
+        if let Some(transfers) = log_map.remove(&transaction.hash) {
+            transaction.transfers.extend(transfers);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTRACT: &str = "0x00000000000000000000000000000000000000cc";
+
+    /// Address or number as a hex word
+    fn word(value: u64) -> String {
+        format!("{:064x}", value)
+    }
+
+    fn log(topics: &[&str], data: &str) -> serde_json::Value {
+        serde_json::json!({
+            "address": CONTRACT,
+            "topics": topics,
+            "data": format!("0x{}", data),
+            "logIndex": "0x5",
+            "transactionHash": "0xab",
+        })
+    }
+
+    /// (recipient, token id, amount) of the recipient sides
+    fn moved(transfers: &[Transfer<Vec<u8>>]) -> Vec<(Vec<u8>, Vec<u8>, String)> {
+        transfers
+            .iter()
+            .filter(|t| t.output)
+            .map(|t| (t.address.clone(), t.asset.name.clone(), t.amount.clone()))
+            .collect()
+    }
+
+    fn topic(value: u64) -> String {
+        format!("0x{}", word(value))
+    }
+
+    fn address_of(value: u8) -> Vec<u8> {
+        let mut address = vec![0; 19];
+        address.push(value);
+        address
+    }
+
+    #[test]
+    fn erc20_transfer() {
+        let amount = "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff";
+        let transfers = transfers(&log(&[TRANSFER, &topic(1), &topic(2)], amount));
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(transfers[0].address, address_of(1));
+        assert!(!transfers[0].output);
+        assert_eq!(transfers[1].address, address_of(2));
+        assert_eq!(transfers[1].asset.kind, shared::AssetKind::Erc20);
+        assert_eq!(
+            transfers[1].asset.contract,
+            decode(&CONTRACT.into()).unwrap()
+        );
+        assert_eq!(transfers[1].index, 5);
+        assert_eq!(
+            transfers[1].amount,
+            "115792089237316195423570985008687907853269984665640564039457584007913129639935"
+        );
+        // Amount word is missing
+        assert!(super::transfers(&log(&[TRANSFER, &topic(1), &topic(2)], "")).is_empty());
+    }
+
+    #[test]
+    fn erc721_transfer() {
+        let transfers = transfers(&log(&[TRANSFER, &topic(1), &topic(2), &topic(0x1234)], ""));
+        assert_eq!(
+            moved(&transfers),
+            vec![(address_of(2), vec![0x12, 0x34], "1".to_string())]
+        );
+        assert_eq!(transfers[1].asset.kind, shared::AssetKind::Erc721);
+    }
+
+    #[test]
+    fn erc1155_single() {
+        let data = format!("{}{}", word(7), word(1000));
+        let transfers = transfers(&log(
+            &[TRANSFER_SINGLE, &topic(9), &topic(1), &topic(2)],
+            &data,
+        ));
+        assert_eq!(
+            moved(&transfers),
+            vec![(address_of(2), vec![7], "1000".to_string())]
+        );
+        assert_eq!(transfers[0].address, address_of(1));
+    }
+
+    #[test]
+    fn erc1155_batch() {
+        let topics = [TRANSFER_BATCH, &topic(9), &topic(1), &topic(2)];
+        // Offsets of both arrays, then ids [7, 8] and values [10, 20]
+        let data = [64, 160, 2, 7, 8, 2, 10, 20]
+            .iter()
+            .map(|v| word(*v))
+            .collect::<String>();
+        let transfers = transfers(&log(&topics, &data));
+        assert_eq!(
+            moved(&transfers),
+            vec![
+                (address_of(2), vec![7], "10".to_string()),
+                (address_of(2), vec![8], "20".to_string()),
+            ]
+        );
+        assert!(transfers.iter().all(|t| t.index == 5));
+        assert_eq!(transfers.iter().filter(|t| !t.output).count(), 2);
+
+        // Empty arrays move nothing
+        let data = [64, 96, 0, 0].iter().map(|v| word(*v)).collect::<String>();
+        assert!(super::transfers(&log(&topics, &data)).is_empty());
+
+        // Arrays of different length or past the data are invalid
+        let data = [64, 160, 2, 7, 8, 1, 10]
+            .iter()
+            .map(|v| word(*v))
+            .collect::<String>();
+        assert!(super::transfers(&log(&topics, &data)).is_empty());
+        let data = [64, 160, 2, 7].iter().map(|v| word(*v)).collect::<String>();
+        assert!(super::transfers(&log(&topics, &data)).is_empty());
+        let data = [64, 1 << 40, 2, 7, 8]
+            .iter()
+            .map(|v| word(*v))
+            .collect::<String>();
+        assert!(super::transfers(&log(&topics, &data)).is_empty());
+    }
+
+    #[test]
+    fn unknown_logs() {
+        // Approval has the same topic count as ERC-20 transfer
+        let approval = "0x8c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b2291e5b200ac8c7c3b925";
+        assert!(transfers(&log(&[approval, &topic(1), &topic(2)], &word(1))).is_empty());
+        assert!(transfers(&log(&[TRANSFER], &word(1))).is_empty());
+        assert!(transfers(&log(&[], "")).is_empty());
+        assert!(transfers(&serde_json::json!({"topics": [TRANSFER]})).is_empty());
+    }
+
+    #[test]
+    fn attach_by_hash() {
+        let mut transaction_list = vec![Transaction {
+            hash: vec![0xab],
+            ..Default::default()
+        }];
+        let logs = vec![
+            log(&[TRANSFER, &topic(1), &topic(2)], &word(3)),
+            serde_json::json!({"transactionHash": "0xcd"}),
+        ];
+        attach(&mut transaction_list, &logs);
+        assert_eq!(transaction_list[0].transfers.len(), 2);
+    }
+}
@@ -0,0 +1,101 @@
+use super::Feed;
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait, Statement};
+
+/// Addresses read in one batch when credentials are filled in afterwards
+const BATCH: i64 = 10000;
+
+/// Credentials of the Shelley address, `stake` is the key or script delegation
+/// credential which groups addresses into a wallet. Kind follows the payment
+/// credential.
+pub struct Credentials {
+    pub kind: shared::AddressKind,
+    pub payment: Vec<u8>,
+    pub delegation: Option<Vec<u8>>,
+    pub stake: Option<Vec<u8>>,
+}
+
+/// Byron addresses are stored as base58 text, they have no credentials
+pub fn credentials(address: &[u8]) -> Option<Credentials> {
+    use pallas_addresses::ShelleyDelegationPart;
+
+    match pallas_addresses::Address::from_bytes(address) {
+        Ok(pallas_addresses::Address::Shelley(shelley)) => {
+            let delegation = shelley.delegation();
+            Some(Credentials {
+                kind: if shelley.payment().is_script() {
+                    shared::AddressKind::Script
+                } else {
+                    shared::AddressKind::Key
+                },
+                payment: shelley.payment().to_vec(),
+                delegation: match delegation {
+                    ShelleyDelegationPart::Null => None,
+                    _ => Some(delegation.to_vec()),
+                },
+                stake: match delegation {
+                    ShelleyDelegationPart::Key(hash) | ShelleyDelegationPart::Script(hash) => {
+                        Some(hash.to_vec())
+                    }
+                    _ => None,
+                },
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Fill in credentials and wallets of addresses stored before, `car wallet <chain id>`
+pub async fn command(db: &DatabaseConnection, args: &[String]) -> Result<(), String> {
+    let chain_id: i32 = match args {
+        [chain] => chain
+            .parse()
+            .map_err(|_| format!("Invalid number: {}", chain))?,
+        _ => return Err(String::from("Usage: car wallet <chain id>")),
+    };
+
+    // Only Cardano addresses have stake credentials
+    let chain = crate::entity::chain::Entity::find_by_id(chain_id)
+        .one(db)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Chain {} not found", chain_id))?;
+    if !matches!(
+        serde_json::from_value(chain.params),
+        Ok(shared::ChainParam::Cardano(_))
+    ) {
+        return Err(format!("Chain {} is not a Cardano chain", chain_id));
+    }
+
+    let mut importer = crate::import::Importer;
+    let mut position = 0;
+    let mut processed = 0;
+    loop {
+        let statement = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"
+            SELECT id, hash FROM address
+            WHERE chain = $1 AND id > $2 AND payment IS NULL
+            ORDER BY id
+            LIMIT $3;
+            "#,
+            vec![chain_id.into(), position.into(), BATCH.into()],
+        );
+        let rows = db.query_all(statement).await.map_err(|e| e.to_string())?;
+        let Some(last) = rows.last() else {
+            break;
+        };
+        position = last.try_get::<i64>("", "id").map_err(|e| e.to_string())?;
+
+        let address_list = rows
+            .iter()
+            .map(|row| row.try_get("", "hash").map_err(|e| e.to_string()))
+            .collect::<Result<Vec<Vec<u8>>, String>>()?;
+        importer.add_wallets(db, chain_id, &address_list).await?;
+
+        processed += rows.len();
+        tracing::info!("Wallets: {} addresses processed", processed);
+    }
+
+    tracing::info!("Wallets finished: {} addresses processed", processed);
+    Ok(())
+}
@@ -0,0 +1,230 @@
+use crate::feed::Feed;
+use sea_orm::{ConnectionTrait, Database, DatabaseConnection, DbBackend, QueryResult, Statement};
+use std::collections::BTreeMap;
+
+/// Carp transactions imported in one batch
+pub const BATCH: i64 = 1000;
+
+const SOURCE: &str = "carp";
+
+/// Address bytes as the Cardano feed stores them, Byron addresses are kept as base58 text
+fn address(payload: Vec<u8>) -> Vec<u8> {
+    match pallas_addresses::Address::from_bytes(&payload) {
+        Ok(pallas_addresses::Address::Byron(byron)) => byron.to_base58().into_bytes(),
+        _ => payload,
+    }
+}
+
+/// Lovelace and native assets of the output CBOR
+fn output_transfers(
+    address: i64,
+    index: i32,
+    era: i32,
+    payload: &[u8],
+) -> Vec<crate::feed::Transfer<i64>> {
+    let era = match pallas_traverse::Era::try_from(era as u16) {
+        Ok(era) => era,
+        Err(_) => return Vec::new(),
+    };
+    let output = match pallas_traverse::MultiEraOutput::decode(era, payload) {
+        Ok(output) => output,
+        Err(err) => {
+            tracing::error!("Carp output not decoded: {}", err);
+            return Vec::new();
+        }
+    };
+
+    output
+        .assets()
+        .into_iter()
+        .map(|asset| {
+            let (asset, amount) = match asset {
+                pallas_traverse::Asset::Ada(amount) => (crate::feed::Asset::default(), amount),
+                pallas_traverse::Asset::NativeAsset(policy, name, amount) => (
+                    crate::feed::Asset {
+                        kind: shared::AssetKind::CardanoToken,
+                        contract: policy.to_vec(),
+                        name,
+                    },
+                    amount,
+                ),
+            };
+            crate::feed::Transfer {
+                address,
+                asset,
+                output: true,
+                index,
+                amount: amount.to_string(),
+                internal: false,
+            }
+        })
+        .collect()
+}
+
+async fn query(
+    carp: &DatabaseConnection,
+    sql: &str,
+    ids: &[i64],
+) -> Result<Vec<QueryResult>, String> {
+    let statement =
+        Statement::from_sql_and_values(DbBackend::Postgres, sql, vec![ids.to_vec().into()]);
+    carp.query_all(statement).await.map_err(|e| e.to_string())
+}
+
+/// Import Carp transactions with their inputs and outputs in batches ordered by
+/// Carp transaction ID. Carp IDs are not kept, addresses and transactions are
+/// mapped by their hashes, so the import can continue next to a running feed.
+pub async fn import(
+    db: &DatabaseConnection,
+    url: &str,
+    chain_id: i32,
+    batch: i64,
+) -> Result<(), String> {
+    let carp = Database::connect(url).await.map_err(|e| e.to_string())?;
+    let mut importer = super::Importer;
+    let mut position = super::position(db, chain_id, SOURCE).await?;
+    let mut imported = 0;
+    tracing::info!("Carp import continues after transaction {}", position);
+
+    loop {
+        let statement = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"
+            SELECT T.id, T.hash, T.tx_index, B.hash AS block, B.height, B.slot, B.era
+            FROM "Transaction" T JOIN "Block" B ON B.id = T.block_id
+            WHERE T.id > $1 AND T.is_valid
+            ORDER BY T.id
+            LIMIT $2;
+            "#,
+            vec![position.into(), batch.into()],
+        );
+        let transactions = carp.query_all(statement).await.map_err(|e| e.to_string())?;
+        if transactions.is_empty() {
+            break;
+        }
+        let ids = transactions
+            .iter()
+            .map(|t| t.try_get::<i64>("", "id"))
+            .collect::<Result<Vec<i64>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        let outputs = query(
+            &carp,
+            r#"
+            SELECT O.tx_id, O.output_index, O.payload, A.payload AS address
+            FROM "TransactionOutput" O JOIN "Address" A ON A.id = O.address_id
+            WHERE O.tx_id = ANY($1)
+            ORDER BY O.tx_id, O.output_index;
+            "#,
+            &ids,
+        )
+        .await?;
+        let inputs = query(
+            &carp,
+            r#"
+            SELECT I.tx_id, A.payload AS address, S.hash AS spent, O.output_index
+            FROM "TransactionInput" I
+                JOIN "Address" A ON A.id = I.address_id
+                JOIN "TransactionOutput" O ON O.id = I.utxo_id
+                JOIN "Transaction" S ON S.id = O.tx_id
+            WHERE I.tx_id = ANY($1)
+            ORDER BY I.tx_id, I.input_index;
+            "#,
+            &ids,
+        )
+        .await?;
+
+        // Blocks
+        let mut eras: BTreeMap<i64, i32> = BTreeMap::new();
+        let mut blocks = Vec::new();
+        for t in transactions.iter() {
+            let id: i64 = t.try_get("", "id").map_err(|e| e.to_string())?;
+            eras.insert(id, t.try_get("", "era").map_err(|e| e.to_string())?);
+            blocks.push(crate::feed::Block {
+                height: t.try_get::<i32>("", "height").map_err(|e| e.to_string())? as u64,
+                slot: Some(t.try_get::<i32>("", "slot").map_err(|e| e.to_string())? as u64),
+                hash: t.try_get("", "block").map_err(|e| e.to_string())?,
+                timestamp: None,
+            });
+        }
+        let block_map = importer.add_blocks(db, chain_id, blocks).await?;
+
+        // Carp address IDs are replaced by the IDs of the same addresses here
+        let address_list = outputs
+            .iter()
+            .chain(inputs.iter())
+            .filter_map(|r| r.try_get::<Vec<u8>>("", "address").ok())
+            .map(address)
+            .collect::<Vec<Vec<u8>>>();
+        importer
+            .add_addresses(db, chain_id, &address_list, None)
+            .await?;
+        importer.add_wallets(db, chain_id, &address_list).await?;
+        let address_map = importer.map_address(db, chain_id, &address_list).await?;
+        let address_id = |row: &QueryResult| -> Option<i64> {
+            let hash = address(row.try_get("", "address").ok()?);
+            address_map.get(&hash).cloned()
+        };
+
+        let mut transaction_map: BTreeMap<i64, crate::feed::UtxoTransaction> = BTreeMap::new();
+        for t in transactions.iter() {
+            let block: Vec<u8> = t.try_get("", "block").map_err(|e| e.to_string())?;
+            transaction_map.insert(
+                t.try_get("", "id").map_err(|e| e.to_string())?,
+                crate::feed::UtxoTransaction {
+                    hash: t.try_get("", "hash").map_err(|e| e.to_string())?,
+                    block: block_map.get(&block).cloned(),
+                    position: t.try_get("", "tx_index").ok(),
+                    ..Default::default()
+                },
+            );
+        }
+        for o in outputs.iter() {
+            let tx_id: i64 = o.try_get("", "tx_id").map_err(|e| e.to_string())?;
+            let index: i32 = o.try_get("", "output_index").map_err(|e| e.to_string())?;
+            let payload: Vec<u8> = o.try_get("", "payload").map_err(|e| e.to_string())?;
+            if let (Some(transaction), Some(address)) =
+                (transaction_map.get_mut(&tx_id), address_id(o))
+            {
+                transaction.to.push(address);
+                transaction.transfers.extend(output_transfers(
+                    address,
+                    index,
+                    eras.get(&tx_id).cloned().unwrap_or_default(),
+                    &payload,
+                ));
+            }
+        }
+        for i in inputs.iter() {
+            let tx_id: i64 = i.try_get("", "tx_id").map_err(|e| e.to_string())?;
+            if let Some(transaction) = transaction_map.get_mut(&tx_id) {
+                if let Some(address) = address_id(i) {
+                    transaction.from.push(address);
+                }
+                transaction.spent.push((
+                    i.try_get("", "spent").map_err(|e| e.to_string())?,
+                    i.try_get::<i32>("", "output_index")
+                        .map_err(|e| e.to_string())? as i64,
+                ));
+            }
+        }
+
+        importer
+            .add_utxo_transactions(db, chain_id, transaction_map.into_values().collect())
+            .await?;
+
+        // Batch is stored, the next run continues after it. Failed batch is
+        // repeated, stored records are skipped by their hashes.
+        position = *ids.last().unwrap();
+        super::save_position(db, chain_id, SOURCE, position).await?;
+        imported += ids.len();
+        tracing::info!(
+            "Carp import: {} transactions, last Carp transaction {}",
+            imported,
+            position
+        );
+    }
+
+    tracing::info!("Carp import finished: {} transactions", imported);
+    Ok(())
+}
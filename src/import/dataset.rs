@@ -0,0 +1,349 @@
+//! Protocol datasets, `dapps.json` and DefiLlama `protocols`, imported as
+//! services and tags of their contract addresses
+use crate::server::native::Format;
+use crate::tag::Tag;
+use sea_orm::{
+    ActiveEnum, ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait, Iterable, Statement,
+};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dataset {
+    Dapps,
+    Llama,
+}
+
+#[derive(Debug, Deserialize)]
+struct Dapps {
+    dapps: Vec<Dapp>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Dapp {
+    title: String,
+    #[serde(default)]
+    desc: String,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    category: Option<String>,
+    #[serde(default)]
+    chains: Vec<String>,
+    #[serde(default)]
+    addresses: Vec<String>,
+}
+
+/// Protocol of the DefiLlama list, `address` may be missing or prefixed by
+/// the chain name like `arbitrum:0x..`
+#[derive(Debug, Deserialize)]
+struct Protocol {
+    name: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    category: Option<String>,
+    #[serde(default)]
+    gecko_id: Option<String>,
+    #[serde(default)]
+    chain: Option<String>,
+    #[serde(default)]
+    chains: Vec<String>,
+    #[serde(default)]
+    address: Option<serde_json::Value>,
+}
+
+/// Service of the dataset, unprefixed addresses belong to every listed chain
+struct Entry {
+    title: String,
+    description: String,
+    url: Option<String>,
+    tags: Vec<String>,
+    chains: Vec<String>,
+    addresses: Vec<String>,
+}
+
+/// What the import changed
+#[derive(Debug, Default)]
+pub struct Report {
+    pub services_created: u64,
+    pub services_merged: u64,
+    pub tags_created: u64,
+    pub addresses_marked: u64,
+    /// Addresses of unknown chains or not valid on their chain
+    pub skipped: u64,
+}
+
+fn entries(dataset: Dataset, text: &str) -> Result<Vec<Entry>, String> {
+    let non_empty = |value: Option<String>| value.filter(|v| !v.is_empty());
+
+    let entries: Vec<Entry> = match dataset {
+        Dataset::Dapps => serde_json::from_str::<Dapps>(text)
+            .map_err(|e| e.to_string())?
+            .dapps
+            .into_iter()
+            .map(|d| Entry {
+                title: d.title,
+                description: d.desc,
+                url: non_empty(d.url),
+                tags: non_empty(d.category).into_iter().collect(),
+                chains: d.chains,
+                addresses: d.addresses,
+            })
+            .collect(),
+        Dataset::Llama => serde_json::from_str::<Vec<Protocol>>(text)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|p| Entry {
+                title: p.name,
+                description: p.description.unwrap_or_default(),
+                url: non_empty(p.url),
+                tags: non_empty(p.category)
+                    .into_iter()
+                    .chain(non_empty(p.gecko_id))
+                    .collect(),
+                // Main chain of the protocol, all of them for multi-chain ones
+                chains: match p.chain {
+                    Some(chain) if chain != "Multi-Chain" => vec![chain],
+                    _ => p.chains,
+                },
+                addresses: match p.address {
+                    Some(serde_json::Value::String(address)) => vec![address],
+                    _ => Vec::new(),
+                },
+            })
+            .collect(),
+    };
+    Ok(entries
+        .into_iter()
+        .filter(|e| !e.title.is_empty())
+        .collect())
+}
+
+/// Chain name without the parenthesized alias, `Polygon (Matic)` is `polygon`
+fn normalize(name: &str) -> String {
+    name.split('(').next().unwrap_or("").trim().to_lowercase()
+}
+
+/// Address bytes of the chain, EVM addresses must have 20 bytes
+fn address(format: Format, value: &str) -> Option<Vec<u8>> {
+    let bytes = crate::server::native::parse(value)?;
+    match format {
+        Format::Evm if bytes.len() != 20 => None,
+        _ => Some(bytes),
+    }
+}
+
+/// IDs of the titles, missing ones are created
+async fn titles(
+    db: &DatabaseConnection,
+    table: &str,
+    titles: &BTreeSet<String>,
+) -> Result<(BTreeMap<String, i32>, u64), String> {
+    let statement = Statement::from_string(
+        DbBackend::Postgres,
+        format!("SELECT id, title FROM {};", table),
+    );
+    let mut map = BTreeMap::new();
+    for row in db.query_all(statement).await.map_err(|e| e.to_string())? {
+        let title: String = row.try_get("", "title").map_err(|e| e.to_string())?;
+        map.entry(title)
+            .or_insert(row.try_get("", "id").map_err(|e| e.to_string())?);
+    }
+
+    let missing = titles
+        .iter()
+        .filter(|t| !map.contains_key(*t))
+        .cloned()
+        .collect::<Vec<String>>();
+    if missing.is_empty() {
+        return Ok((map, 0));
+    }
+
+    // Values of the `Tag` enum are stored in address tags, new tags go above them
+    if table == "tag" {
+        let reserved = Tag::iter().map(|t| t.to_value()).max().unwrap_or_default();
+        let statement = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"
+            SELECT setval(
+                pg_get_serial_sequence('tag', 'id'),
+                GREATEST(nextval(pg_get_serial_sequence('tag', 'id')), $1)
+            )
+            "#,
+            vec![(reserved as i64).into()],
+        );
+        db.execute(statement).await.map_err(|e| e.to_string())?;
+    }
+
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        &format!(
+            "INSERT INTO {} (title) SELECT unnest($1::text[]) RETURNING id, title;",
+            table
+        ),
+        vec![missing.into()],
+    );
+    let mut created = 0;
+    for row in db.query_all(statement).await.map_err(|e| e.to_string())? {
+        map.insert(
+            row.try_get("", "title").map_err(|e| e.to_string())?,
+            row.try_get("", "id").map_err(|e| e.to_string())?,
+        );
+        created += 1;
+    }
+    Ok((map, created))
+}
+
+/// Import the dataset, `chain_names` maps dataset chain names to chain IDs
+/// where the chain title doesn't match
+pub async fn import(
+    db: &DatabaseConnection,
+    dataset: Dataset,
+    text: &str,
+    chain_names: &BTreeMap<String, i32>,
+) -> Result<Report, String> {
+    let entries = entries(dataset, text)?;
+    let chain_list = crate::entity::chain::Entity::find()
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    let formats = chain_list
+        .iter()
+        .map(|c| (c.id, Format::from_params(&c.params)))
+        .collect::<BTreeMap<i32, Format>>();
+    let mut chains = chain_list
+        .iter()
+        .map(|c| (normalize(&c.title), c.id))
+        .collect::<BTreeMap<String, i32>>();
+    chains.extend(chain_names.iter().map(|(n, id)| (normalize(n), *id)));
+
+    let mut report = Report::default();
+    let (tags, created) = titles(
+        db,
+        "tag",
+        &entries
+            .iter()
+            .flat_map(|e| e.tags.iter().cloned())
+            .collect(),
+    )
+    .await?;
+    report.tags_created = created;
+    let mut existing = titles(db, "service", &BTreeSet::new()).await?.0;
+
+    for entry in entries.iter() {
+        let tag_ids = entry
+            .tags
+            .iter()
+            .filter_map(|t| tags.get(t).cloned())
+            .collect::<Vec<i32>>();
+
+        // Address is prefixed by its chain or belongs to every chain of the entry
+        let mut addresses: BTreeMap<i32, Vec<Vec<u8>>> = BTreeMap::new();
+        for value in entry.addresses.iter() {
+            let value = value.trim();
+            let (names, value) = match value.split_once(':') {
+                Some((prefix, rest)) => (vec![prefix.to_string()], rest.trim()),
+                None => (entry.chains.clone(), value),
+            };
+            for name in names.iter() {
+                let parsed = chains.get(&normalize(name)).and_then(|chain| {
+                    address(formats.get(chain).cloned().unwrap_or_default(), value)
+                        .map(|bytes| (*chain, bytes))
+                });
+                match parsed {
+                    Some((chain, bytes)) => addresses.entry(chain).or_default().push(bytes),
+                    None => report.skipped += 1,
+                }
+            }
+        }
+        if addresses.is_empty() {
+            continue;
+        }
+
+        // Service is merged by title, description and URL only fill the missing ones
+        let statement = Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"
+            INSERT INTO service (id, title, description, url, tags)
+            VALUES (COALESCE($1, nextval(pg_get_serial_sequence('service', 'id'))), $2, $3, $4, $5)
+            ON CONFLICT (id) DO UPDATE
+                SET
+                    description = CASE
+                        WHEN service.description = '' THEN EXCLUDED.description
+                        ELSE service.description
+                    END,
+                    url = COALESCE(service.url, EXCLUDED.url),
+                    tags = ARRAY(
+                        SELECT DISTINCT V FROM unnest(service.tags || EXCLUDED.tags) AS U(V) ORDER BY V
+                    )
+            RETURNING id
+            "#,
+            vec![
+                existing.get(&entry.title).cloned().into(),
+                entry.title.clone().into(),
+                entry.description.clone().into(),
+                entry.url.clone().into(),
+                tag_ids.clone().into(),
+            ],
+        );
+        let service: i32 = match db.query_one(statement).await {
+            Ok(Some(row)) => row.try_get("", "id").map_err(|e| e.to_string())?,
+            Ok(None) => continue,
+            Err(err) => return Err(err.to_string()),
+        };
+        if existing.insert(entry.title.clone(), service).is_some() {
+            report.services_merged += 1;
+        } else {
+            report.services_created += 1;
+        }
+
+        for (chain_id, address_list) in addresses {
+            // Contract hashes mark the addresses once feeds store them, the
+            // stored ones are marked right away
+            let statement = Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                r#"
+                INSERT INTO service_hash (service, chain, role, hash)
+                SELECT $1, $2, $3, unnest($4::bytea[])
+                ON CONFLICT DO NOTHING
+                "#,
+                vec![
+                    service.into(),
+                    chain_id.into(),
+                    (crate::service::registry::Role::Contract as i32).into(),
+                    address_list.clone().into(),
+                ],
+            );
+            db.execute(statement).await.map_err(|e| e.to_string())?;
+
+            let statement = Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                r#"
+                UPDATE address
+                SET
+                    services = ARRAY(
+                        SELECT DISTINCT V FROM unnest(services || ARRAY[$1]) AS U(V) ORDER BY V
+                    ),
+                    tags = ARRAY(SELECT DISTINCT V FROM unnest(tags || $2) AS U(V) ORDER BY V)
+                WHERE chain = $3 AND hash = ANY($4)
+                "#,
+                vec![
+                    service.into(),
+                    tag_ids.clone().into(),
+                    chain_id.into(),
+                    address_list.into(),
+                ],
+            );
+            report.addresses_marked += db
+                .execute(statement)
+                .await
+                .map_err(|e| e.to_string())?
+                .rows_affected();
+        }
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,380 @@
+use crate::feed::Feed;
+use sea_orm::{DatabaseConnection, EntityTrait};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Rows stored in one batch
+pub const BATCH: usize = 1000;
+
+/// Errors kept in the report, the rest is only counted
+const MAX_ERRORS: usize = 100;
+
+/// Transaction row of the dump, CSV has the same columns in the same order
+/// with addresses separated by `;`
+#[derive(Debug, Deserialize)]
+struct Row {
+    chain: i32,
+    hash: String,
+    #[serde(default)]
+    inputs: Vec<String>,
+    #[serde(default)]
+    outputs: Vec<String>,
+    amount: Option<serde_json::Value>,
+    timestamp: Option<i64>,
+}
+
+/// Fields of the CSV line, quoted field may contain commas and `""` quotes
+fn csv_fields(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().unwrap();
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(String::from("Unterminated quoted field"));
+    }
+    Ok(fields.iter().map(|f| f.trim().to_string()).collect())
+}
+
+fn csv_row(line: &str) -> Result<Row, String> {
+    let fields = csv_fields(line)?;
+    if !(4..=6).contains(&fields.len()) {
+        return Err(format!("Expected 4 to 6 columns, got {}", fields.len()));
+    }
+    let list = |field: &str| {
+        field
+            .split(';')
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+            .map(String::from)
+            .collect()
+    };
+    let optional = |index: usize| fields.get(index).filter(|f| !f.is_empty());
+
+    Ok(Row {
+        chain: super::parse(&fields[0])?,
+        hash: fields[1].to_string(),
+        inputs: list(&fields[2]),
+        outputs: list(&fields[3]),
+        amount: optional(4).map(|a| serde_json::Value::String(a.to_string())),
+        timestamp: optional(5).map(|t| super::parse(t)).transpose()?,
+    })
+}
+
+fn row(format: shared::ImportFormat, line: &str) -> Result<Row, String> {
+    match format {
+        shared::ImportFormat::Jsonl => serde_json::from_str(line).map_err(|e| e.to_string()),
+        shared::ImportFormat::Csv => csv_row(line),
+    }
+}
+
+/// Address bytes as the feeds store them, hex for EVM and Bitcoin, bech32 or
+/// Byron base58 for Cardano
+fn address(value: &str) -> Result<Vec<u8>, String> {
+    if let Ok(bytes) = hex::decode(value.trim_start_matches("0x")) {
+        Ok(bytes)
+    } else if let Ok(address) = pallas_addresses::Address::from_bech32(value) {
+        Ok(address.to_vec())
+    } else if pallas_addresses::ByronAddress::from_base58(value).is_ok() {
+        Ok(value.as_bytes().to_vec())
+    } else {
+        Err(format!("Invalid address: {}", value))
+    }
+}
+
+/// Decimal amount, `0x` prefixed hex is converted
+fn amount(value: Option<serde_json::Value>) -> Result<Option<String>, String> {
+    let amount = match value {
+        None | Some(serde_json::Value::Null) => return Ok(None),
+        Some(serde_json::Value::Number(number)) => number.as_u64().map(|n| n.to_string()),
+        Some(serde_json::Value::String(text)) if text.starts_with("0x") => {
+            crate::feed::hex_to_decimal(&text)
+        }
+        Some(serde_json::Value::String(text))
+            if !text.is_empty() && text.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            Some(text)
+        }
+        Some(value) => return Err(format!("Invalid amount: {}", value)),
+    };
+    amount
+        .map(Some)
+        .ok_or_else(|| String::from("Invalid amount"))
+}
+
+fn transaction(row: Row) -> Result<(i32, crate::feed::Transaction), String> {
+    let hash = hex::decode(row.hash.trim_start_matches("0x"))
+        .map_err(|_| format!("Invalid hash: {}", row.hash))?;
+    let from = row
+        .inputs
+        .iter()
+        .map(|a| address(a))
+        .collect::<Result<Vec<Vec<u8>>, String>>()?;
+    let to = row
+        .outputs
+        .iter()
+        .map(|a| address(a))
+        .collect::<Result<Vec<Vec<u8>>, String>>()?;
+    // Amount is a single transfer, it isn't known how it splits among several
+    // inputs or outputs
+    let amount = amount(row.amount)?;
+    if amount.is_some() && (from.len() != 1 || to.len() != 1) {
+        return Err(String::from(
+            "Amount needs exactly one input and one output, leave it out otherwise",
+        ));
+    }
+
+    Ok((
+        row.chain,
+        crate::feed::Transaction {
+            hash,
+            transfers: crate::feed::native_transfers(&from, &to, amount, 0),
+            from,
+            to,
+            block: None,
+            timestamp: row.timestamp,
+            position: None,
+        },
+    ))
+}
+
+/// Store the batch, transactions already stored or seen earlier in the dump
+/// are counted as existing
+async fn store(
+    db: &DatabaseConnection,
+    batch: &mut BTreeMap<i32, crate::feed::TransactionList>,
+    seen: &mut BTreeSet<(i32, Vec<u8>)>,
+    cardano: &BTreeSet<i32>,
+    report: &mut shared::ImportReport,
+) {
+    let mut importer = super::Importer;
+    for (chain_id, transaction_list) in std::mem::take(batch) {
+        let stored = match importer
+            .map_transactions(
+                db,
+                chain_id,
+                transaction_list.iter().map(|t| t.hash.clone()).collect(),
+            )
+            .await
+        {
+            Ok(stored) => stored,
+            Err(err) => {
+                report.errors.push(format!("Chain {}: {}", chain_id, err));
+                continue;
+            }
+        };
+        for t in transaction_list.iter() {
+            if stored.contains_key(&t.hash) || !seen.insert((chain_id, t.hash.clone())) {
+                report.existing += 1;
+            } else {
+                report.transactions += 1;
+            }
+        }
+
+        if report.dry_run {
+            continue;
+        }
+        let address_list = transaction_list
+            .iter()
+            .flat_map(|t| t.from.iter().chain(t.to.iter()).cloned())
+            .collect::<BTreeSet<Vec<u8>>>()
+            .into_iter()
+            .collect::<Vec<Vec<u8>>>();
+        let result = match importer
+            .add_transactions(db, chain_id, transaction_list)
+            .await
+        {
+            Ok(()) if cardano.contains(&chain_id) => {
+                importer.add_wallets(db, chain_id, &address_list).await
+            }
+            result => result,
+        };
+        if let Err(err) = result {
+            report.errors.push(format!("Chain {}: {}", chain_id, err));
+        }
+    }
+
+    tracing::info!(
+        "Transaction import: {} rows, {} new transactions, {} existing, {} invalid",
+        report.rows,
+        report.transactions,
+        report.existing,
+        report.invalid
+    );
+}
+
+/// Import transaction dump through the same address and transaction mapping
+/// as the feeds, dry run validates rows and counts new transactions only
+pub async fn import(
+    db: &DatabaseConnection,
+    text: &str,
+    format: shared::ImportFormat,
+    dry_run: bool,
+) -> Result<shared::ImportReport, String> {
+    let chain_list = crate::entity::chain::Entity::find()
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    let chains = chain_list.iter().map(|c| c.id).collect::<BTreeSet<i32>>();
+    // Addresses of Cardano chains are grouped into wallets
+    let cardano = chain_list
+        .iter()
+        .filter(|c| {
+            matches!(
+                serde_json::from_value(c.params.clone()),
+                Ok(shared::ChainParam::Cardano(_))
+            )
+        })
+        .map(|c| c.id)
+        .collect::<BTreeSet<i32>>();
+
+    let mut report = shared::ImportReport {
+        dry_run,
+        ..Default::default()
+    };
+    let mut batch: BTreeMap<i32, crate::feed::TransactionList> = BTreeMap::new();
+    let mut seen = BTreeSet::new();
+    let mut pending = 0;
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        // CSV header is optional
+        if line.is_empty() || (format == shared::ImportFormat::Csv && line.starts_with("chain,")) {
+            continue;
+        }
+        report.rows += 1;
+
+        match row(format, line).and_then(transaction) {
+            Ok((chain_id, _)) if !chains.contains(&chain_id) => {
+                report.invalid += 1;
+                if report.errors.len() < MAX_ERRORS {
+                    report
+                        .errors
+                        .push(format!("Line {}: Unknown chain {}", number + 1, chain_id));
+                }
+            }
+            Ok((chain_id, t)) => {
+                batch.entry(chain_id).or_default().push(t);
+                pending += 1;
+            }
+            Err(err) => {
+                report.invalid += 1;
+                if report.errors.len() < MAX_ERRORS {
+                    report.errors.push(format!("Line {}: {}", number + 1, err));
+                }
+            }
+        }
+
+        if pending >= BATCH {
+            store(db, &mut batch, &mut seen, &cardano, &mut report).await;
+            pending = 0;
+        }
+    }
+    store(db, &mut batch, &mut seen, &cardano, &mut report).await;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_quoted_fields() {
+        let row = csv_row(r#"1,"0xab","0x01;0x02", "0x03",1000,1680000000"#).unwrap();
+        assert_eq!(row.chain, 1);
+        assert_eq!(row.hash, "0xab");
+        assert_eq!(row.inputs, vec!["0x01", "0x02"]);
+        assert_eq!(row.outputs, vec!["0x03"]);
+        assert_eq!(row.amount, Some(serde_json::json!("1000")));
+        assert_eq!(row.timestamp, Some(1680000000));
+
+        let fields = csv_fields(r#"1,"a,b","say ""hi""",,"#).unwrap();
+        assert_eq!(fields, vec!["1", "a,b", r#"say "hi""#, "", ""]);
+        assert!(csv_fields(r#"1,"0xab"#).is_err());
+    }
+
+    #[test]
+    fn csv_optional_columns() {
+        let row = csv_row("1,0xab,0x01,0x02").unwrap();
+        assert_eq!(row.amount, None);
+        assert_eq!(row.timestamp, None);
+
+        let row = csv_row("1,0xab,,0x02,,").unwrap();
+        assert!(row.inputs.is_empty());
+        assert_eq!(row.amount, None);
+        assert_eq!(row.timestamp, None);
+
+        assert!(csv_row("1,0xab,0x01").is_err());
+        assert!(csv_row("1,0xab,0x01,0x02,1,2,3").is_err());
+        assert!(csv_row("x,0xab,0x01,0x02").is_err());
+        assert!(csv_row("1,0xab,0x01,0x02,1,never").is_err());
+    }
+
+    #[test]
+    fn amount_forms() {
+        let json = |text: &str| Some(serde_json::from_str(text).unwrap());
+        assert_eq!(amount(None), Ok(None));
+        assert_eq!(amount(json("null")), Ok(None));
+        assert_eq!(amount(json("1000")), Ok(Some("1000".to_string())));
+        assert_eq!(amount(json(r#""1000""#)), Ok(Some("1000".to_string())));
+        assert_eq!(amount(json(r#""0x3e8""#)), Ok(Some("1000".to_string())));
+        // Hex and decimal strings keep full precision
+        assert_eq!(
+            amount(json(r#""0xffffffffffffffffffffffffffffffff""#)),
+            Ok(Some("340282366920938463463374607431768211455".to_string()))
+        );
+        assert_eq!(
+            amount(json(r#""340282366920938463463374607431768211455""#)),
+            Ok(Some("340282366920938463463374607431768211455".to_string()))
+        );
+    }
+
+    #[test]
+    fn amount_invalid() {
+        let json = |text: &str| Some(serde_json::from_str(text).unwrap());
+        // JSON numbers out of the u64 range lose precision, they are rejected
+        assert!(amount(json("18446744073709551616")).is_err());
+        assert!(amount(json("-1")).is_err());
+        assert!(amount(json("1.5")).is_err());
+        assert!(amount(json(r#""0xzz""#)).is_err());
+        assert!(amount(json(r#""-1""#)).is_err());
+        assert!(amount(json(r#""1e3""#)).is_err());
+        assert!(amount(json(r#""""#)).is_err());
+        assert!(amount(json("true")).is_err());
+    }
+
+    #[test]
+    fn amount_needs_single_input_and_output() {
+        let row = |inputs: &[&str], outputs: &[&str]| Row {
+            chain: 1,
+            hash: "0xab".to_string(),
+            inputs: inputs.iter().map(|a| a.to_string()).collect(),
+            outputs: outputs.iter().map(|a| a.to_string()).collect(),
+            amount: Some(serde_json::json!("1000")),
+            timestamp: None,
+        };
+        let (_, t) = transaction(row(&["0x01"], &["0x02"])).unwrap();
+        assert_eq!(t.transfers.len(), 2);
+        assert!(t.transfers.iter().all(|t| t.amount == "1000"));
+        assert!(transaction(row(&["0x01"], &["0x02", "0x03"])).is_err());
+        assert!(transaction(row(&["0x01", "0x02"], &["0x03"])).is_err());
+
+        let (_, t) = transaction(Row {
+            amount: None,
+            ..row(&["0x01"], &["0x02", "0x03"])
+        })
+        .unwrap();
+        assert!(t.transfers.is_empty());
+    }
+}
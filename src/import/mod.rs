@@ -0,0 +1,120 @@
+use async_trait::async_trait;
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, Statement};
+use std::collections::BTreeMap;
+
+mod carp;
+mod dataset;
+pub mod file;
+
+/// Importer stores records through the same methods as the feeds
+pub struct Importer;
+
+#[async_trait]
+impl crate::feed::Feed for Importer {}
+
+/// Run import command, `car import <source> ...`
+pub async fn command(db: &DatabaseConnection, args: &[String]) -> Result<(), String> {
+    match args.first().map(|a| a.as_str()) {
+        Some("carp") => match args {
+            [_, url, chain] => carp::import(db, url, parse(chain)?, carp::BATCH).await,
+            [_, url, chain, batch] => carp::import(db, url, parse(chain)?, parse(batch)?).await,
+            _ => Err(String::from(
+                "Usage: car import carp <carp database url> <chain id> [batch size]",
+            )),
+        },
+        Some("file") => {
+            let (path, dry_run) = match args {
+                [_, path] => (path, false),
+                [_, path, flag] if flag == "--dry-run" => (path, true),
+                _ => {
+                    return Err(String::from(
+                        "Usage: car import file <jsonl or csv file> [--dry-run]",
+                    ))
+                }
+            };
+            let format = if path.ends_with(".csv") {
+                shared::ImportFormat::Csv
+            } else {
+                shared::ImportFormat::Jsonl
+            };
+            let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+
+            let report = file::import(db, &text, format, dry_run).await?;
+            for error in report.errors.iter() {
+                tracing::error!("{}", error);
+            }
+            tracing::info!("Transaction import finished: {:?}", report);
+            Ok(())
+        }
+        Some(source @ ("dapps" | "llama")) => {
+            let dataset = if source == "dapps" {
+                dataset::Dataset::Dapps
+            } else {
+                dataset::Dataset::Llama
+            };
+            let (path, names) = match args {
+                [_, path, names @ ..] => (path, names),
+                _ => {
+                    return Err(String::from(
+                        "Usage: car import dapps|llama <json file> [chain name=chain id ...]",
+                    ))
+                }
+            };
+            let chain_names = names
+                .iter()
+                .map(|n| match n.rsplit_once('=') {
+                    Some((name, id)) => Ok((name.to_string(), parse(id)?)),
+                    None => Err(format!("Invalid chain name: {}", n)),
+                })
+                .collect::<Result<BTreeMap<String, i32>, String>>()?;
+            let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+
+            let report = dataset::import(db, dataset, &text, &chain_names).await?;
+            tracing::info!("Dataset import finished: {:?}", report);
+            Ok(())
+        }
+        _ => Err(String::from("Usage: car import carp|file|dapps|llama ...")),
+    }
+}
+
+fn parse<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid number: {}", value))
+}
+
+/// Last imported record of the source, zero if the import didn't start yet
+async fn position(db: &DatabaseConnection, chain_id: i32, source: &str) -> Result<i64, String> {
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"SELECT position FROM import WHERE chain = $1 AND source = $2;"#,
+        vec![chain_id.into(), source.into()],
+    );
+
+    match db.query_one(statement).await {
+        Ok(Some(row)) => row.try_get("", "position").map_err(|e| e.to_string()),
+        Ok(None) => Ok(0),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+async fn save_position(
+    db: &DatabaseConnection,
+    chain_id: i32,
+    source: &str,
+    position: i64,
+) -> Result<(), String> {
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        INSERT INTO import (chain, source, position, updated) VALUES ($1, $2, $3, now())
+        ON CONFLICT (chain, source) DO UPDATE SET position = $3, updated = now();
+        "#,
+        vec![chain_id.into(), source.into(), position.into()],
+    );
+
+    db.execute(statement)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
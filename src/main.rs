@@ -1,6 +1,5 @@
 #![recursion_limit = "256"]
 
-use feed::Feed;
 use pallas_addresses::Address;
 use sea_orm::{prelude::*, ConnectOptions, Database};
 use std::{
@@ -15,6 +14,7 @@ use tracing_subscriber::prelude::*;
 pub mod common;
 pub mod entity;
 pub mod feed;
+pub mod import;
 pub mod server;
 pub mod service;
 pub mod tag;
@@ -52,6 +52,17 @@ async fn main() -> Result<(), String> {
         .set_schema_search_path("public".into());
     let db: DatabaseConnection = Database::connect(opt).await.unwrap();
 
+    // One shot commands, server runs without arguments
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(command) = args.first() {
+        return match command.as_str() {
+            "import" => import::command(&db, &args[1..]).await,
+            "wallet" => feed::wallet::command(&db, &args[1..]).await,
+            "services" => service::registry::command(&db, &args[1..]).await,
+            _ => Err(format!("Unknown command: {}", command)),
+        };
+    }
+
     /*
     // Some test queries
     let address = entity::prelude::AddressEntity::find_by_id(36111266)
@@ -71,12 +82,6 @@ async fn main() -> Result<(), String> {
     );
     */
 
-    /*
-    for service in service::init_services(&db.clone()).await.iter() {
-        service.clone().mark_addresses(db.clone()).await.unwrap();
-    }
-    */
-
     /*
     let set1 = common::address_interacting(
         db.clone(),
@@ -85,6 +90,7 @@ async fn main() -> Result<(), String> {
         None,
         Some(vec![1]),
         None,
+        false,
         common::DirectionOfInteraction::From,
     )
     .await
@@ -97,6 +103,7 @@ async fn main() -> Result<(), String> {
         None,
         Some(vec![2]),
         None,
+        false,
         common::DirectionOfInteraction::From,
     )
     .await
@@ -109,6 +116,7 @@ async fn main() -> Result<(), String> {
         None,
         Some(vec![3]),
         None,
+        false,
         common::DirectionOfInteraction::From,
     )
     .await
@@ -122,6 +130,14 @@ async fn main() -> Result<(), String> {
     */
     //let bind: SocketAddr = ;
 
+    // Registry is optional, services may be managed only through the API
+    let registry = service::registry::directory();
+    if registry.is_dir() {
+        if let Err(err) = service::registry::sync(&db, &registry).await {
+            tracing::error!("Service registry not synced: {}", err);
+        }
+    }
+
     let mut feed_channel: FeedChannel = Arc::new(RwLock::new(HashMap::new()));
 
     if let Ok(chains) = entity::chain::Entity::find().all(&db).await {
@@ -145,35 +161,24 @@ pub async fn start_feeder(
     feed_channel: FeedChannel,
     chain: entity::chain::Model,
 ) {
-    let (sender, receiver) = tokio::sync::mpsc::channel::<feed::FeedCommand>(16);
-    let db = db.clone();
-    let mut feed_channel = feed_channel.write().await;
     let params: shared::ChainParam = serde_json::from_value(chain.params).unwrap();
-    match params {
-        shared::ChainParam::ArbiScan(mut anyscan) => {
-            feed_channel.insert(chain.id.clone(), sender);
-            tokio::task::spawn(async move {
-                anyscan.run(db, receiver, chain.id).await;
-            });
-        }
-        shared::ChainParam::EtherScan(mut anyscan) => {
-            feed_channel.insert(chain.id.clone(), sender);
-            tokio::task::spawn(async move {
-                anyscan.run(db, receiver, chain.id).await;
-            });
-        }
-        shared::ChainParam::PolyScan(mut anyscan) => {
-            feed_channel.insert(chain.id.clone(), sender);
-            tokio::task::spawn(async move {
-                anyscan.run(db, receiver, chain.id).await;
-            });
-        }
-        shared::ChainParam::Cardano(mut cardano) => {
-            feed_channel.insert(chain.id.clone(), sender);
-            tokio::task::spawn(async move {
-                cardano.run(db, receiver, chain.id).await;
-            });
-        }
-        shared::ChainParam::None => {}
+    if chain.disabled {
+        tracing::info!("Chain {} disabled, feed not started", chain.id);
+        return;
+    }
+    if let shared::ChainParam::None = params {
+        return;
     }
+
+    let (sender, receiver) = tokio::sync::mpsc::channel::<feed::FeedCommand>(16);
+    let weak = sender.downgrade();
+    feed_channel.write().await.insert(chain.id, sender);
+    // Supervisor holds only a weak sender, removed feed sees a closed channel
+    tokio::task::spawn(feed::supervisor::supervise(
+        db,
+        feed_channel,
+        chain.id,
+        weak,
+        receiver,
+    ));
 }
@@ -4,6 +4,7 @@ use sea_orm::{
     ActiveModelTrait, ActiveValue, Condition, DatabaseConnection, DbBackend, EntityTrait,
     ModelTrait, Statement,
 };
+use std::collections::BTreeMap;
 /// Create address endpoint
 #[post("/api/address/")]
 #[openapi(description = "Create address record")]
@@ -19,13 +20,18 @@ pub async fn create(
     }
 
     let body = body.into_inner();
+    let hash = match super::native::parse(&body.hash) {
+        Some(hash) => hash,
+        None => return Err(reject::custom(super::BadRequest)),
+    };
+    let formats = super::native::formats(&db).await;
 
     let value = address::ActiveModel {
         title: ActiveValue::Set(body.title.clone()),
         chain: ActiveValue::Set(body.chain),
         services: ActiveValue::Set(body.services.clone()),
         tags: ActiveValue::Set(body.tags.clone()),
-        hash: ActiveValue::Set(hex::decode(body.hash).unwrap()),
+        hash: ActiveValue::Set(hash),
         ..Default::default()
     }
     .insert(&db)
@@ -36,9 +42,15 @@ pub async fn create(
             id: Some(new.id),
             title: new.title,
             chain: new.chain.clone(),
+            native: super::native::render(
+                formats.get(&new.chain).cloned().unwrap_or_default(),
+                &new.hash,
+            ),
             hash: hex::encode(new.hash),
             services: body.services.clone(),
             tags: body.tags.clone(),
+            wallet: new.wallet_id,
+            kind: shared::AddressKind::from_i32(new.kind),
         }
         .into()),
         _ => Err(reject::not_found()),
@@ -58,10 +70,15 @@ pub async fn detail(
     {
         Ok(Some(a)) => {
             tracing::info!("output: {:?}", a);
+            let formats = super::native::formats(&db).await;
             Ok(shared::Address {
                 id: Some(a.id),
                 title: a.title.clone(),
                 hash: hex::encode(&a.hash),
+                native: super::native::render(
+                    formats.get(&a.chain).cloned().unwrap_or_default(),
+                    &a.hash,
+                ),
                 services: a
                     .services
                     .iter()
@@ -75,6 +92,8 @@ pub async fn detail(
                     .map(|s| s.clone())
                     .collect::<Vec<i32>>(),
                 chain: a.chain.clone(),
+                wallet: a.wallet_id,
+                kind: shared::AddressKind::from_i32(a.kind),
             }
             .into())
         }
@@ -83,43 +102,48 @@ pub async fn detail(
 }
 
 #[post("/api/address/{id}/process")]
-#[openapi(description = "Read address record")]
+#[openapi(description = "Queue address processing job")]
 pub async fn process(
-    #[data] feed_channel: crate::FeedChannel,
     #[data] db: DatabaseConnection,
     #[data] token: String,
     #[header = "authorization"] authorization: String,
     id: i64,
-) -> Result<Json<bool>, Rejection> {
+) -> Result<Json<shared::Job>, Rejection> {
     if !authorization.ends_with(&token) {
         return Err(reject::custom(super::Unauthorized));
     }
 
-    if let Ok(Some(address)) = crate::entity::address::Entity::find_by_id(id)
-        .one(&db)
-        .await
-    {
-        let feed_channel = feed_channel.read().await;
-        if let Some(feed) = feed_channel.get(&address.chain) {
-            if let Err(_) = feed
-                .send(crate::feed::FeedCommand::Address(address.id))
-                .await
-            {
-                return Ok(true.into());
-            } else {
-                return Ok(true.into());
-            }
-        };
+    match address::Entity::find_by_id(id).one(&db).await {
+        Ok(Some(_)) => {}
+        _ => return Err(reject::not_found()),
+    }
+
+    match super::job::enqueue(&db, vec![id]).await {
+        Ok(list) => match list.into_iter().next() {
+            Some(job) => Ok(job.into()),
+            // Feed of the chain has no per address index
+            None => Err(reject::custom(super::BadRequest)),
+        },
+        Err(err) => {
+            tracing::error!("{}", err);
+            Err(reject::custom(super::InternalError))
+        }
     }
-    Ok(false.into())
 }
 
-pub fn address_list_query(list: Vec<address::Model>) -> Vec<shared::Address> {
+pub fn address_list_query(
+    list: Vec<address::Model>,
+    formats: &BTreeMap<i32, super::native::Format>,
+) -> Vec<shared::Address> {
     list.iter()
         .map(|a| shared::Address {
             id: Some(a.id),
             title: a.title.clone(),
             hash: hex::encode(&a.hash),
+            native: super::native::render(
+                formats.get(&a.chain).cloned().unwrap_or_default(),
+                &a.hash,
+            ),
             services: a
                 .services
                 .iter()
@@ -133,6 +157,8 @@ pub fn address_list_query(list: Vec<address::Model>) -> Vec<shared::Address> {
                 .map(|s| s.clone())
                 .collect::<Vec<i32>>(),
             chain: a.chain.clone(),
+            wallet: a.wallet_id,
+            kind: shared::AddressKind::from_i32(a.kind),
         })
         .collect::<Vec<shared::Address>>()
 }
@@ -143,21 +169,14 @@ pub fn address_list_query(list: Vec<address::Model>) -> Vec<shared::Address> {
 pub async fn list_by_address(
     #[data] db: DatabaseConnection,
     address: String,
+    query: Query<shared::AddressQuery>,
 ) -> Result<Json<Vec<shared::Address>>, Rejection> {
+    let kind = query.into_inner().kind.map(|k| k as i32);
     tracing::info!("By address");
 
-    let mut bin_address: Option<Vec<u8>> = None;
-    let mut address = address.to_lowercase();
-
-    if address.starts_with("0x") {
-        address = String::from(&address[2..]);
-    } else {
-        tracing::info!("not match 0x");
-    }
-
-    if let Ok(bytes) = hex::decode(address) {
-        bin_address = Some(bytes);
-    } else {
+    // Hex, bech32, Byron base58 and checksummed EVM addresses are accepted
+    let bin_address = super::native::parse(&address);
+    if bin_address.is_none() {
         tracing::info!("Address is not convertable");
     }
 
@@ -165,13 +184,16 @@ pub async fn list_by_address(
         match address::Entity::find()
             .from_raw_sql(Statement::from_sql_and_values(
                 DbBackend::Postgres,
-                r#"SELECT * from address WHERE hash = $1;"#,
-                vec![address.into()],
+                r#"SELECT * from address WHERE hash = $1 AND ($2::integer IS NULL OR kind = $2);"#,
+                vec![address.into(), kind.into()],
             ))
             .all(&db)
             .await
         {
-            Ok(list) => return Ok(address_list_query(list).into()),
+            Ok(list) => {
+                let formats = super::native::formats(&db).await;
+                return Ok(address_list_query(list, &formats).into());
+            }
             _ => {
                 tracing::error!("Failed2");
                 return Err(reject::not_found());
@@ -186,21 +208,38 @@ pub async fn list_by_address(
 
 /// Get address list by tag id endpoint
 #[get("/api/address/by_tag_id/{id}")]
-#[openapi(description = "Read address list by tag id")]
+#[openapi(description = "Read address list by tag id, optionally with its descendant tags")]
 pub async fn list_by_tag(
     #[data] db: DatabaseConnection,
     id: i32,
+    query: Query<shared::AddressQuery>,
 ) -> Result<Json<Vec<shared::Address>>, Rejection> {
+    let query = query.into_inner();
+    let kind = query.kind.map(|k| k as i32);
+    let tags = if query.descendants.unwrap_or(false) {
+        match crate::tag::descendants(&db, &[id]).await {
+            Ok(tags) => tags,
+            Err(err) => {
+                tracing::error!("{}", err);
+                return Err(reject::custom(super::InternalError));
+            }
+        }
+    } else {
+        vec![id]
+    };
     match address::Entity::find()
         .from_raw_sql(Statement::from_sql_and_values(
             DbBackend::Postgres,
-            r#"SELECT * from address WHERE tags @> ARRAY[$1];"#,
-            vec![id.into()],
+            r#"SELECT * from address WHERE tags && $1 AND ($2::integer IS NULL OR kind = $2);"#,
+            vec![tags.into(), kind.into()],
         ))
         .all(&db)
         .await
     {
-        Ok(list) => Ok(address_list_query(list).into()),
+        Ok(list) => {
+            let formats = super::native::formats(&db).await;
+            Ok(address_list_query(list, &formats).into())
+        }
         _ => Err(reject::not_found()),
     }
 }
@@ -211,17 +250,22 @@ pub async fn list_by_tag(
 pub async fn list_by_service(
     #[data] db: DatabaseConnection,
     id: i32,
+    query: Query<shared::AddressQuery>,
 ) -> Result<Json<Vec<shared::Address>>, Rejection> {
+    let kind = query.into_inner().kind.map(|k| k as i32);
     match address::Entity::find()
         .from_raw_sql(Statement::from_sql_and_values(
             DbBackend::Postgres,
-            r#"SELECT * from address WHERE services @> ARRAY[$1];"#,
-            vec![id.into()],
+            r#"SELECT * from address WHERE services @> ARRAY[$1] AND ($2::integer IS NULL OR kind = $2);"#,
+            vec![id.into(), kind.into()],
         ))
         .all(&db)
         .await
     {
-        Ok(list) => Ok(address_list_query(list).into()),
+        Ok(list) => {
+            let formats = super::native::formats(&db).await;
+            Ok(address_list_query(list, &formats).into())
+        }
         _ => Err(reject::not_found()),
     }
 }
@@ -232,17 +276,22 @@ pub async fn list_by_service(
 pub async fn list_by_transaction(
     #[data] db: DatabaseConnection,
     id: i32,
+    query: Query<shared::AddressQuery>,
 ) -> Result<Json<Vec<shared::Address>>, Rejection> {
+    let kind = query.into_inner().kind.map(|k| k as i32);
     match address::Entity::find()
         .from_raw_sql(Statement::from_sql_and_values(
             DbBackend::Postgres,
-            r#"SELECT * from address WHERE tags @> ARRAY[$1];"#,
-            vec![id.into()],
+            r#"SELECT * from address WHERE tags @> ARRAY[$1] AND ($2::integer IS NULL OR kind = $2);"#,
+            vec![id.into(), kind.into()],
         ))
         .all(&db)
         .await
     {
-        Ok(list) => Ok(address_list_query(list).into()),
+        Ok(list) => {
+            let formats = super::native::formats(&db).await;
+            Ok(address_list_query(list, &formats).into())
+        }
         _ => Err(reject::not_found()),
     }
 }
@@ -270,14 +319,21 @@ pub async fn update(
             value.tags = ActiveValue::Set(body.tags.clone());
             value.services = ActiveValue::Set(body.services.clone());
             let value: address::Model = value.update(&db).await.unwrap();
+            let formats = super::native::formats(&db).await;
 
             Ok(shared::Address {
                 id: Some(value.id),
                 title: value.title.clone(),
                 chain: value.chain,
                 hash: hex::encode(&value.hash),
+                native: super::native::render(
+                    formats.get(&value.chain).cloned().unwrap_or_default(),
+                    &value.hash,
+                ),
                 tags: value.tags.clone(),
                 services: value.services.clone(),
+                wallet: value.wallet_id,
+                kind: shared::AddressKind::from_i32(value.kind),
             }
             .into())
         }
@@ -303,9 +359,12 @@ pub async fn delete(
         id: Some(1),
         title: Some(String::from("test")),
         hash: hex::encode(vec![0, 0]),
+        native: String::new(),
         services: vec![1],
         tags: vec![1],
         chain: 1,
+        wallet: None,
+        kind: shared::AddressKind::Unknown,
     };
     Ok(address.into())
 }
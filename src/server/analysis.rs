@@ -6,54 +6,201 @@ use sea_orm::{
 };
 use std::collections::{BTreeMap, BTreeSet};
 
+/// Count one edge, every input address sends to every output address
+#[allow(clippy::too_many_arguments)]
+fn process_edge(
+    address_id: &i64,
+    addresses_from: Vec<i64>,
+    addresses_to: Vec<i64>,
+    address_list: &mut BTreeSet<i64>,
+    inputs: &mut BTreeMap<i64, i32>,
+    outputs: &mut BTreeMap<i64, i32>,
+    mixed_in: &mut BTreeMap<i64, i32>,
+    mixed_out: &mut BTreeMap<i64, i32>,
+) {
+    let mixed_in_state = addresses_from.contains(&address_id);
+    let mixed_out_state = addresses_to.contains(&address_id);
+
+    // Proces input adress
+    for address in addresses_from {
+        // Add input only, if the original address is present in output
+        address_list.insert(address);
+        if mixed_out_state {
+            inputs.insert(address, inputs.get(&address).unwrap_or(&0) + 1);
+        }
+        // Add mixin only if is original address present in input
+        if mixed_in_state && address != *address_id {
+            mixed_in.insert(address, mixed_in.get(&address).unwrap_or(&0) + 1);
+        }
+    }
+
+    // Proces output adress
+    for address in addresses_to {
+        address_list.insert(address);
+        // Add output only if the original address is present in input
+        if mixed_in_state {
+            outputs.insert(address, outputs.get(&address).unwrap_or(&0) + 1);
+        }
+        // Add mixin only if is original address present in output
+        if mixed_out_state && address != *address_id {
+            mixed_out.insert(address, mixed_out.get(&address).unwrap_or(&0) + 1);
+        }
+    }
+}
+
+/// Addresses analysed together, the whole wallet of the address in the wallet mode
+async fn members(db: &DatabaseConnection, address_id: i64, mode: shared::RelationMode) -> Vec<i64> {
+    if mode == shared::RelationMode::Address {
+        return vec![address_id];
+    }
+
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        SELECT id FROM address
+        WHERE wallet_id = (SELECT wallet_id FROM address WHERE id = $1)
+        ORDER BY id;
+        "#,
+        vec![address_id.into()],
+    );
+    match db.query_all(statement).await {
+        Ok(query) if !query.is_empty() => query
+            .iter()
+            .map(|row| row.try_get("", "id").unwrap())
+            .collect(),
+        _ => vec![address_id],
+    }
+}
+
+/// Address standing for the wallet of each address, the one with the lowest ID
+async fn wallets(db: &DatabaseConnection, address_list: &BTreeSet<i64>) -> BTreeMap<i64, i64> {
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        SELECT A.id, (SELECT min(B.id) FROM address B WHERE B.wallet_id = A.wallet_id) AS wallet
+        FROM address A
+        WHERE A.id = ANY($1) AND A.wallet_id IS NOT NULL;
+        "#,
+        vec![address_list.iter().cloned().collect::<Vec<i64>>().into()],
+    );
+    let mut map = BTreeMap::new();
+    if let Ok(query) = db.query_all(statement).await {
+        for row in query.iter() {
+            map.insert(
+                row.try_get("", "id").unwrap(),
+                row.try_get("", "wallet").unwrap(),
+            );
+        }
+    }
+    map
+}
+
+/// Count edges of the members, in the wallet mode addresses of one wallet are
+/// merged into the address standing for it
+#[allow(clippy::too_many_arguments)]
 async fn process_query(
     db: &DatabaseConnection,
     address_id: &i64,
+    members: &[i64],
+    mode: shared::RelationMode,
     address_list: &mut BTreeSet<i64>,
     inputs: &mut BTreeMap<i64, i32>,
     outputs: &mut BTreeMap<i64, i32>,
     mixed_in: &mut BTreeMap<i64, i32>,
     mixed_out: &mut BTreeMap<i64, i32>,
+    internal: &mut BTreeMap<i64, i32>,
 ) {
+    // Input and output addresses of each edge, internal ones come from contract calls
+    let mut edges: Vec<(Vec<i64>, Vec<i64>, bool)> = Vec::new();
+
     let statement = Statement::from_sql_and_values(
         DbBackend::Postgres,
-        r#"SELECT "from", "to" FROM transaction WHERE $1 = ANY("from") or $1 = ANY("to");"#,
-        vec![address_id.clone().into()],
+        r#"SELECT "from", "to" FROM transaction WHERE "from" && $1 or "to" && $1;"#,
+        vec![members.to_vec().into()],
     );
     if let Ok(query) = db.query_all(statement).await {
         for row in query.iter() {
-            let addresses_from: Vec<i64> = row.try_get("", "from").unwrap();
-            let addresses_to: Vec<i64> = row.try_get("", "to").unwrap();
-
-            let mixed_in_state = addresses_from.contains(&address_id);
-            let mixed_out_state = addresses_to.contains(&address_id);
-
-            // Proces input adress
-            for address in addresses_from {
-                // Add input only, if the original address is present in output
-                address_list.insert(address);
-                if mixed_out_state {
-                    inputs.insert(address, inputs.get(&address).unwrap_or(&0) + 1);
-                }
-                // Add mixin only if is original address present in input
-                if mixed_in_state && address != *address_id {
-                    mixed_in.insert(address, mixed_in.get(&address).unwrap_or(&0) + 1);
-                }
+            edges.push((
+                row.try_get("", "from").unwrap(),
+                row.try_get("", "to").unwrap(),
+                false,
+            ));
+        }
+    }
+
+    // ERC token movements are separate edges tagged with the token contract, both
+    // sides of one movement share the log index. Internal contract calls are edges
+    // of their own too.
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        SELECT
+            array_agg(X.address_id) FILTER (WHERE NOT X.output) AS "from",
+            array_agg(X.address_id) FILTER (WHERE X.output) AS "to",
+            bool_or(X.internal) AS internal
+        FROM transfer X
+        WHERE (X.transaction_id, X.asset_id, X.index) IN (
+            SELECT Y.transaction_id, Y.asset_id, Y.index
+            FROM transfer Y JOIN asset A ON A.id = Y.asset_id
+            WHERE Y.address_id = ANY($1) AND (A.kind IN (1, 2, 3) OR Y.internal)
+        )
+        GROUP BY X.transaction_id, X.asset_id, X.index;
+        "#,
+        vec![members.to_vec().into()],
+    );
+    if let Ok(query) = db.query_all(statement).await {
+        for row in query.iter() {
+            edges.push((
+                row.try_get("", "from").unwrap_or_default(),
+                row.try_get("", "to").unwrap_or_default(),
+                row.try_get("", "internal").unwrap_or_default(),
+            ));
+        }
+    }
+
+    let wallets = match mode {
+        shared::RelationMode::Address => BTreeMap::new(),
+        shared::RelationMode::Wallet => {
+            let mut address_list: BTreeSet<i64> = members.iter().cloned().collect();
+            for (from, to, _) in edges.iter() {
+                address_list.extend(from.iter().chain(to.iter()));
             }
+            wallets(db, &address_list).await
+        }
+    };
+    let merge = |addresses: Vec<i64>| -> Vec<i64> {
+        if wallets.is_empty() {
+            return addresses;
+        }
+        BTreeSet::from_iter(
+            addresses
+                .into_iter()
+                .map(|a| wallets.get(&a).cloned().unwrap_or(a)),
+        )
+        .into_iter()
+        .collect()
+    };
+    let address_id = wallets.get(address_id).unwrap_or(address_id);
 
-            // Proces output adress
-            for address in addresses_to {
-                address_list.insert(address);
-                // Add output only if the original address is present in input
-                if mixed_in_state {
-                    outputs.insert(address, outputs.get(&address).unwrap_or(&0) + 1);
-                }
-                // Add mixin only if is original address present in output
-                if mixed_out_state && address != *address_id {
-                    mixed_out.insert(address, mixed_out.get(&address).unwrap_or(&0) + 1);
+    for (from, to, is_internal) in edges {
+        let (from, to) = (merge(from), merge(to));
+        if is_internal {
+            for address in BTreeSet::from_iter(from.iter().chain(to.iter())) {
+                if address != address_id {
+                    *internal.entry(*address).or_default() += 1;
                 }
             }
         }
+        process_edge(
+            address_id,
+            from,
+            to,
+            address_list,
+            inputs,
+            outputs,
+            mixed_in,
+            mixed_out,
+        );
     }
 }
 
@@ -62,8 +209,10 @@ async fn process_query(
 pub async fn relation(
     #[data] db: DatabaseConnection,
     address: String,
+    query: Query<shared::RelationQuery>,
 ) -> Result<Json<shared::AddressRelation>, Rejection> {
-    if let Ok(address_hex) = hex::decode(&address) {
+    let mode = query.into_inner().mode.unwrap_or_default();
+    if let Some(address_hex) = super::native::parse(&address) {
         // Get address ID
         let statement = Statement::from_sql_and_values(
             DbBackend::Postgres,
@@ -78,18 +227,34 @@ pub async fn relation(
                 let mut outputs: BTreeMap<i64, i32> = BTreeMap::new();
                 let mut mixed_in: BTreeMap<i64, i32> = BTreeMap::new();
                 let mut mixed_out: BTreeMap<i64, i32> = BTreeMap::new();
+                let mut internal: BTreeMap<i64, i32> = BTreeMap::new();
 
                 let address_id: i64 = result.try_get("", "id").unwrap();
                 address_list.insert(address_id);
+                let members = members(&db, address_id, mode).await;
 
                 process_query(
                     &db,
                     &address_id,
+                    &members,
+                    mode,
                     &mut address_list,
                     &mut inputs,
                     &mut outputs,
                     &mut mixed_in,
                     &mut mixed_out,
+                    &mut internal,
+                )
+                .await;
+
+                let mut input_amounts: BTreeMap<i64, Vec<shared::Amount>> = BTreeMap::new();
+                let mut output_amounts: BTreeMap<i64, Vec<shared::Amount>> = BTreeMap::new();
+                transform::map_amounts(
+                    &db,
+                    &members,
+                    mode,
+                    &mut input_amounts,
+                    &mut output_amounts,
                 )
                 .await;
 
@@ -98,12 +263,29 @@ pub async fn relation(
                 let address_detail = address_map.get(&address_id).unwrap();
                 Ok(shared::AddressRelation {
                     id: address_id.clone(),
-                    hex: address.clone(),
+                    hex: hex::encode(&address_hex),
+                    native: address_detail.native.clone(),
                     human: address_detail.title.clone(),
-                    inputs: transform::address_ref(&address_map, inputs),
-                    outputs: transform::address_ref(&address_map, outputs),
-                    mixed_in: transform::address_ref(&address_map, mixed_in),
-                    mixed_out: transform::address_ref(&address_map, mixed_out),
+                    wallet: address_detail.wallet,
+                    inputs: transform::address_ref(&address_map, inputs, &input_amounts, &internal),
+                    outputs: transform::address_ref(
+                        &address_map,
+                        outputs,
+                        &output_amounts,
+                        &internal,
+                    ),
+                    mixed_in: transform::address_ref(
+                        &address_map,
+                        mixed_in,
+                        &BTreeMap::new(),
+                        &internal,
+                    ),
+                    mixed_out: transform::address_ref(
+                        &address_map,
+                        mixed_out,
+                        &BTreeMap::new(),
+                        &internal,
+                    ),
                     tags: address_detail.tags.clone(),
                     services: address_detail.services.clone(),
                 }
@@ -120,8 +302,10 @@ pub async fn relation(
 pub async fn relation_human(
     #[data] db: DatabaseConnection,
     address: String,
+    query: Query<shared::RelationQuery>,
 ) -> Result<Json<shared::AddressRelationHuman>, Rejection> {
-    if let Ok(address_hex) = hex::decode(&address) {
+    let mode = query.into_inner().mode.unwrap_or_default();
+    if let Some(address_hex) = super::native::parse(&address) {
         // Get address ID
         let statement = Statement::from_sql_and_values(
             DbBackend::Postgres,
@@ -141,18 +325,34 @@ pub async fn relation_human(
                 let mut outputs: BTreeMap<i64, i32> = BTreeMap::new();
                 let mut mixed_in: BTreeMap<i64, i32> = BTreeMap::new();
                 let mut mixed_out: BTreeMap<i64, i32> = BTreeMap::new();
+                let mut internal: BTreeMap<i64, i32> = BTreeMap::new();
 
                 let address_id: i64 = result.try_get("", "id").unwrap();
                 address_list.insert(address_id);
+                let members = members(&db, address_id, mode).await;
 
                 process_query(
                     &db,
                     &address_id,
+                    &members,
+                    mode,
                     &mut address_list,
                     &mut inputs,
                     &mut outputs,
                     &mut mixed_in,
                     &mut mixed_out,
+                    &mut internal,
+                )
+                .await;
+
+                let mut input_amounts: BTreeMap<i64, Vec<shared::Amount>> = BTreeMap::new();
+                let mut output_amounts: BTreeMap<i64, Vec<shared::Amount>> = BTreeMap::new();
+                transform::map_amounts(
+                    &db,
+                    &members,
+                    mode,
+                    &mut input_amounts,
+                    &mut output_amounts,
                 )
                 .await;
 
@@ -172,31 +372,41 @@ pub async fn relation_human(
                 let address_detail = address_map.get(&address_id).unwrap();
                 Ok(shared::AddressRelationHuman {
                     id: address_id.clone(),
-                    hex: address.clone(),
+                    hex: hex::encode(&address_hex),
+                    native: address_detail.native.clone(),
                     human: address_detail.title.clone(),
+                    wallet: address_detail.wallet,
                     inputs: transform::address_ref_human(
                         &address_map,
                         &tag_map,
                         &service_map,
                         inputs,
+                        &input_amounts,
+                        &internal,
                     ),
                     outputs: transform::address_ref_human(
                         &address_map,
                         &tag_map,
                         &service_map,
                         outputs,
+                        &output_amounts,
+                        &internal,
                     ),
                     mixed_in: transform::address_ref_human(
                         &address_map,
                         &tag_map,
                         &service_map,
                         mixed_in,
+                        &BTreeMap::new(),
+                        &internal,
                     ),
                     mixed_out: transform::address_ref_human(
                         &address_map,
                         &tag_map,
                         &service_map,
                         mixed_out,
+                        &BTreeMap::new(),
+                        &internal,
                     ),
                     tags: address_detail
                         .tags
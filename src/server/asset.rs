@@ -0,0 +1,103 @@
+use crate::server::transform;
+use rweb::*;
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, Statement};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Sum transfers of the policy per address and asset, `order` selects holders or movers
+async fn policy_flow(
+    db: &DatabaseConnection,
+    policy: &[u8],
+    order: &str,
+    limit: i64,
+) -> Result<Vec<shared::AssetFlow>, String> {
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        &format!(
+            r#"
+            SELECT
+                F.address_id, F.asset, F.chain, F.kind, F.contract, F.name,
+                F.received::text AS received, F.sent::text AS sent,
+                (F.received - F.sent)::text AS balance, F.transactions
+            FROM (
+                SELECT
+                    X.address_id, A.id AS asset, A.chain, A.kind, A.contract, A.name,
+                    COALESCE(sum(X.amount) FILTER (WHERE X.output), 0) AS received,
+                    COALESCE(sum(X.amount) FILTER (WHERE NOT X.output), 0) AS sent,
+                    count(DISTINCT X.transaction_id) AS transactions
+                FROM transfer X JOIN asset A ON A.id = X.asset_id
+                WHERE A.kind = $1 AND A.contract = $2
+                GROUP BY X.address_id, A.id
+            ) F
+            {}
+            LIMIT $3;
+            "#,
+            order
+        ),
+        vec![
+            (shared::AssetKind::CardanoToken as i32).into(),
+            policy.to_vec().into(),
+            limit.into(),
+        ],
+    );
+
+    let query = db.query_all(statement).await.map_err(|e| e.to_string())?;
+
+    let mut address_list: BTreeSet<i64> = query
+        .iter()
+        .map(|row| row.try_get("", "address_id").unwrap())
+        .collect();
+    let mut address_map: BTreeMap<i64, shared::PrivAddress> = BTreeMap::new();
+    transform::map_addresses(db, &mut address_list, &mut address_map).await;
+
+    Ok(query
+        .iter()
+        .map(|row| {
+            let id: i64 = row.try_get("", "address_id").unwrap();
+            let address = address_map.get(&id);
+            shared::AssetFlow {
+                id,
+                hex: address.map(|a| hex::encode(&a.hash)).unwrap_or_default(),
+                native: address.map(|a| a.native.clone()).unwrap_or_default(),
+                human: address.map(|a| a.title.clone()).unwrap_or_default(),
+                asset: transform::asset(row),
+                received: row.try_get("", "received").unwrap(),
+                sent: row.try_get("", "sent").unwrap(),
+                balance: row.try_get("", "balance").unwrap(),
+                transactions: row.try_get("", "transactions").unwrap(),
+            }
+        })
+        .collect())
+}
+
+#[get("/api/asset/policy/{policy}")]
+#[openapi(description = "Holders and movers of Cardano native assets under policy id")]
+pub async fn policy(
+    #[data] db: DatabaseConnection,
+    policy: String,
+    query: Query<shared::ListQuery>,
+) -> Result<Json<shared::PolicyRelation>, Rejection> {
+    let policy_bytes = hex::decode(&policy).map_err(|_| reject::custom(super::BadRequest))?;
+    let limit = query.into_inner().limit.unwrap_or(100).clamp(1, 1000);
+
+    let holders = policy_flow(
+        &db,
+        &policy_bytes,
+        "WHERE F.received > F.sent ORDER BY F.received - F.sent DESC",
+        limit,
+    )
+    .await;
+    let movers = policy_flow(&db, &policy_bytes, "ORDER BY F.transactions DESC", limit).await;
+
+    match (holders, movers) {
+        (Ok(holders), Ok(movers)) => Ok(shared::PolicyRelation {
+            policy,
+            holders,
+            movers,
+        }
+        .into()),
+        (Err(err), _) | (_, Err(err)) => {
+            tracing::error!("{}", err);
+            Err(reject::custom(super::InternalError))
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use crate::entity::block;
+use rweb::*;
+use sea_orm::{DatabaseConnection, EntityTrait};
+
+#[get("/api/block/{id}")]
+#[openapi(description = "Read block record")]
+pub async fn detail(
+    #[data] db: DatabaseConnection,
+    id: String,
+) -> Result<Json<shared::Block>, Rejection> {
+    let id = id.parse::<i64>().map_err(|_| reject::not_found())?;
+    match block::Entity::find_by_id(id).one(&db).await {
+        Ok(Some(b)) => Ok(shared::Block {
+            id: b.id,
+            chain: b.chain,
+            height: b.height,
+            slot: b.slot,
+            hash: hex::encode(&b.hash),
+            timestamp: b.timestamp.map(|t| t.timestamp()),
+        }
+        .into()),
+        _ => Err(reject::not_found()),
+    }
+}
@@ -2,6 +2,88 @@ use crate::entity::chain;
 use rweb::*;
 use sea_orm::{ActiveModelTrait, ActiveValue, DatabaseConnection, EntityTrait, ModelTrait};
 
+fn chain_query(value: chain::Model) -> shared::Chain {
+    shared::Chain {
+        id: Some(value.id),
+        title: value.title,
+        params: serde_json::from_value(value.params).unwrap_or_default(),
+        disabled: value.disabled,
+    }
+}
+
+/// Params of the update, the feed keeps its progress (`last`, `slot`,
+/// `block_hash`) unless the caller rewinds it
+fn configured(body: &shared::Chain, stored: &chain::Model, rewind: bool) -> shared::ChainParam {
+    use shared::ChainParam::*;
+
+    let mut params = body.params.clone();
+    if rewind {
+        return params;
+    }
+    let stored: shared::ChainParam =
+        serde_json::from_value(stored.params.clone()).unwrap_or_default();
+    match (&mut params, stored) {
+        (EtherScan(new), EtherScan(old))
+        | (PolyScan(new), PolyScan(old))
+        | (ArbiScan(new), ArbiScan(old)) => new.last = old.last,
+        (EvmRpc(new), EvmRpc(old)) => new.last = old.last,
+        (Bitcoin(new), Bitcoin(old)) => new.last = old.last,
+        (Cardano(new), Cardano(old)) => {
+            new.slot = old.slot;
+            new.block_hash = old.block_hash;
+        }
+        _ => {}
+    }
+    params
+}
+
+/// Stop the feed, store the new params and start the feed again. Progress is
+/// taken once the feed stops and the chain keeps its feed even if the params
+/// can't be stored.
+async fn restart(
+    db: DatabaseConnection,
+    feed_channel: crate::FeedChannel,
+    body: shared::Chain,
+    rewind: bool,
+    id: i32,
+) -> Result<chain::Model, String> {
+    crate::feed::supervisor::stop(&feed_channel, id).await;
+    let stored = match chain::Entity::find_by_id(id).one(&db).await {
+        Ok(Some(stored)) => stored,
+        Ok(None) => return Err(format!("Chain {} not found", id)),
+        Err(err) => return Err(err.to_string()),
+    };
+
+    let mut value: chain::ActiveModel = stored.clone().into();
+    value.title = ActiveValue::Set(body.title.clone());
+    value.params =
+        ActiveValue::Set(serde_json::to_value(configured(&body, &stored, rewind)).unwrap());
+    value.disabled = ActiveValue::Set(body.disabled);
+    let result = value.update(&db).await.map_err(|e| e.to_string());
+    super::native::invalidate();
+
+    let chain = match &result {
+        Ok(chain) => chain.clone(),
+        Err(_) => stored,
+    };
+    crate::start_feeder(db, feed_channel, chain).await;
+    result
+}
+
+/// Params are stored only when their source answers, disabled chain is not
+/// probed as its feed doesn't start
+async fn validate(body: &shared::Chain) -> Result<(), Rejection> {
+    if body.disabled {
+        return Ok(());
+    }
+    crate::feed::supervisor::probe(&body.params)
+        .await
+        .map_err(|err| {
+            tracing::error!("Chain params rejected: {}", err);
+            reject::custom(super::BadRequest)
+        })
+}
+
 #[post("/api/chain/")]
 #[openapi(description = "Create chain record")]
 pub async fn create(
@@ -16,27 +98,23 @@ pub async fn create(
     }
 
     let body = body.into_inner();
+    validate(&body).await?;
 
     let value = chain::ActiveModel {
         id: ActiveValue::NotSet,
         title: ActiveValue::Set(body.title.clone()),
-        params: ActiveValue::Set(
-            serde_json::to_value(shared::ChainParam::PolyScan(shared::AnyScan::default())).unwrap(),
-        ),
+        params: ActiveValue::Set(serde_json::to_value(&body.params).unwrap()),
+        disabled: ActiveValue::Set(body.disabled),
     }
     .insert(&db)
     .await;
 
     match value {
         Ok(new) => {
+            super::native::invalidate();
             crate::start_feeder(db.clone(), feed_channel.clone(), new.clone()).await;
 
-            Ok(shared::Chain {
-                id: Some(new.id),
-                title: new.title,
-                params: shared::ChainParam::default(),
-            }
-            .into())
+            Ok(chain_query(new).into())
         }
         _ => Err(reject::not_found()),
     }
@@ -49,12 +127,7 @@ pub async fn detail(
     id: i32,
 ) -> Result<Json<shared::Chain>, Rejection> {
     match chain::Entity::find_by_id(id).one(&db).await {
-        Ok(Some(value)) => Ok(shared::Chain {
-            id: Some(value.id),
-            title: value.title.clone(),
-            params: serde_json::from_value(value.params).unwrap(),
-        }
-        .into()),
+        Ok(Some(value)) => Ok(chain_query(value).into()),
         _ => Err(reject::not_found()),
     }
 }
@@ -64,12 +137,8 @@ pub async fn detail(
 pub async fn list(#[data] db: DatabaseConnection) -> Result<Json<Vec<shared::Chain>>, Rejection> {
     match chain::Entity::find().all(&db).await {
         Ok(chain_list) => Ok(chain_list
-            .iter()
-            .map(|ch| shared::Chain {
-                id: Some(ch.id),
-                title: ch.title.clone(),
-                params: serde_json::from_value(ch.params.clone()).unwrap(),
-            })
+            .into_iter()
+            .map(chain_query)
             .collect::<Vec<shared::Chain>>()
             .into()),
         _ => Err(reject::not_found()),
@@ -81,9 +150,11 @@ pub async fn list(#[data] db: DatabaseConnection) -> Result<Json<Vec<shared::Cha
 pub async fn update(
     #[data] token: String,
     #[data] db: DatabaseConnection,
+    #[data] feed_channel: crate::FeedChannel,
     #[header = "authorization"] authorization: String,
 
     body: Json<shared::Chain>,
+    query: Query<shared::ChainQuery>,
     id: i32,
 ) -> Result<Json<shared::Chain>, Rejection> {
     if !authorization.ends_with(&token) {
@@ -91,22 +162,59 @@ pub async fn update(
     }
 
     let body = body.into_inner();
+    let rewind = query.into_inner().rewind.unwrap_or(false);
 
-    match chain::Entity::find_by_id(id).one(&db).await {
-        Ok(Some(value)) => {
-            let mut value: chain::ActiveModel = value.into();
+    let value = match chain::Entity::find_by_id(id).one(&db).await {
+        Ok(Some(value)) => value,
+        _ => return Err(reject::not_found()),
+    };
 
-            value.title = ActiveValue::Set(body.title.clone());
-            let value: chain::Model = value.update(&db).await.unwrap();
+    // Feed restarts only when its configuration changes
+    let stored: shared::ChainParam =
+        serde_json::from_value(value.params.clone()).unwrap_or_default();
+    let params = configured(&body, &value, rewind);
+    let changed = serde_json::to_value(&stored).ok() != serde_json::to_value(&params).ok()
+        || value.disabled != body.disabled;
+    if changed {
+        validate(&shared::Chain {
+            params: params.clone(),
+            ..body.clone()
+        })
+        .await?;
+    } else {
+        let mut value: chain::ActiveModel = value.into();
+        value.title = ActiveValue::Set(body.title.clone());
+        return match value.update(&db).await {
+            Ok(value) => Ok(chain_query(value).into()),
+            Err(err) => {
+                tracing::error!("{}", err);
+                Err(reject::custom(super::InternalError))
+            }
+        };
+    }
 
+    // Restart goes on in the background when the feed is busy for long, the
+    // params are stored as soon as the feed stops
+    let task = tokio::task::spawn(restart(db, feed_channel, body.clone(), rewind, id));
+    match tokio::time::timeout(crate::feed::supervisor::STOP_TIMEOUT, task).await {
+        Ok(Ok(Ok(value))) => Ok(chain_query(value).into()),
+        Ok(Ok(Err(err))) => {
+            tracing::error!("{}", err);
+            Err(reject::custom(super::InternalError))
+        }
+        Ok(Err(err)) => {
+            tracing::error!("{}", err);
+            Err(reject::custom(super::InternalError))
+        }
+        Err(_) => {
+            tracing::info!("Chain {} params are stored once its feed stops", id);
             Ok(shared::Chain {
-                title: value.title.clone(),
-                id: Some(value.id),
-                params: serde_json::from_value(value.params).unwrap(),
+                id: Some(id),
+                params,
+                ..body
             }
             .into())
         }
-        _ => Err(reject::not_found()),
     }
 }
 
@@ -126,14 +234,160 @@ pub async fn delete(
 
     match chain::Entity::find_by_id(id).one(&db).await {
         Ok(Some(value)) => {
-            value.delete(&db).await.unwrap();
-            let mut feed_channel = feed_channel.write().await;
-            if let Some(channel) = feed_channel.get(&id) {
-                channel.send(crate::feed::FeedCommand::Stop).await;
-                feed_channel.remove(&id);
+            let stop = crate::feed::supervisor::stop(&feed_channel, id);
+            if tokio::time::timeout(crate::feed::supervisor::STOP_TIMEOUT, stop)
+                .await
+                .is_err()
+            {
+                tracing::error!("Feed {} didn't stop in time", id);
             }
+            value.delete(&db).await.unwrap();
+            super::native::invalidate();
             Ok(().into())
         }
         _ => Err(reject::not_found()),
     }
 }
+
+#[get("/api/chain/{id}/status")]
+#[openapi(description = "Read chain feed status")]
+pub async fn status(
+    #[data] db: DatabaseConnection,
+    #[data] feed_channel: crate::FeedChannel,
+    id: i32,
+) -> Result<Json<shared::FeedStatus>, Rejection> {
+    let disabled = match chain::Entity::find_by_id(id).one(&db).await {
+        Ok(Some(value)) => value.disabled,
+        _ => return Err(reject::not_found()),
+    };
+
+    let stopped = crate::feed::crash_status(id);
+    if disabled {
+        return Ok(shared::FeedStatus {
+            state: shared::FeedState::Disabled,
+            ..stopped
+        }
+        .into());
+    }
+    let sender = match feed_channel.read().await.get(&id) {
+        Some(sender) => sender.clone(),
+        None => return Ok(stopped.into()),
+    };
+
+    // Feed answers between blocks, slow block may keep it busy and its
+    // channel full
+    let (reply, receiver) = tokio::sync::oneshot::channel();
+    let status = async {
+        sender
+            .send(crate::feed::FeedCommand::Status(reply))
+            .await
+            .ok()?;
+        receiver.await.ok()
+    };
+    match tokio::time::timeout(tokio::time::Duration::from_secs(10), status).await {
+        Ok(Some(status)) => Ok(status.into()),
+        Ok(None) => Ok(stopped.into()),
+        Err(_) => Ok(shared::FeedStatus {
+            state: shared::FeedState::Unresponsive,
+            ..stopped
+        }
+        .into()),
+    }
+}
+
+/// Pass command to the running feed of the chain
+async fn command(
+    feed_channel: crate::FeedChannel,
+    id: i32,
+    command: crate::feed::FeedCommand,
+) -> Result<Json<()>, Rejection> {
+    let sender = match feed_channel.read().await.get(&id) {
+        Some(sender) => sender.clone(),
+        None => return Err(reject::custom(super::NotFound)),
+    };
+
+    match sender.send(command).await {
+        Ok(()) => Ok(().into()),
+        Err(_) => Err(reject::custom(super::NotFound)),
+    }
+}
+
+#[post("/api/chain/{id}/pause")]
+#[openapi(description = "Stop processing new blocks of the chain")]
+pub async fn pause(
+    #[data] token: String,
+    #[data] feed_channel: crate::FeedChannel,
+    #[header = "authorization"] authorization: String,
+    id: i32,
+) -> Result<Json<()>, Rejection> {
+    if !authorization.ends_with(&token) {
+        return Err(reject::custom(super::Unauthorized));
+    }
+
+    command(feed_channel, id, crate::feed::FeedCommand::Pause).await
+}
+
+#[post("/api/chain/{id}/resume")]
+#[openapi(description = "Continue processing blocks of the paused chain")]
+pub async fn resume(
+    #[data] token: String,
+    #[data] feed_channel: crate::FeedChannel,
+    #[header = "authorization"] authorization: String,
+    id: i32,
+) -> Result<Json<()>, Rejection> {
+    if !authorization.ends_with(&token) {
+        return Err(reject::custom(super::Unauthorized));
+    }
+
+    command(feed_channel, id, crate::feed::FeedCommand::Resume).await
+}
+
+#[post("/api/chain/{id}/rewind")]
+#[openapi(description = "Continue processing the chain from the given height")]
+pub async fn rewind(
+    #[data] token: String,
+    #[data] feed_channel: crate::FeedChannel,
+    #[header = "authorization"] authorization: String,
+    body: Json<shared::Rewind>,
+    id: i32,
+) -> Result<Json<()>, Rejection> {
+    if !authorization.ends_with(&token) {
+        return Err(reject::custom(super::Unauthorized));
+    }
+
+    let body = body.into_inner();
+    command(
+        feed_channel,
+        id,
+        crate::feed::FeedCommand::Rewind(body.height),
+    )
+    .await
+}
+
+#[post("/api/chain/{id}/backfill")]
+#[openapi(description = "Process the inclusive block range again")]
+pub async fn backfill(
+    #[data] token: String,
+    #[data] feed_channel: crate::FeedChannel,
+    #[header = "authorization"] authorization: String,
+    body: Json<shared::Backfill>,
+    id: i32,
+) -> Result<Json<()>, Rejection> {
+    if !authorization.ends_with(&token) {
+        return Err(reject::custom(super::Unauthorized));
+    }
+
+    let body = body.into_inner();
+    if body.from > body.to {
+        return Err(reject::custom(super::BadRequest));
+    }
+    command(
+        feed_channel,
+        id,
+        crate::feed::FeedCommand::Backfill {
+            from: body.from,
+            to: body.to,
+        },
+    )
+    .await
+}
@@ -0,0 +1,37 @@
+use rweb::*;
+use sea_orm::DatabaseConnection;
+
+#[post("/api/import/")]
+#[openapi(description = "Import JSONL or CSV transaction dump")]
+pub async fn upload(
+    #[data] db: DatabaseConnection,
+    #[data] token: String,
+    #[header = "authorization"] authorization: String,
+    query: Query<shared::ImportQuery>,
+    #[body] body: rweb::hyper::body::Bytes,
+) -> Result<Json<shared::ImportReport>, Rejection> {
+    if !authorization.ends_with(&token) {
+        return Err(reject::custom(super::Unauthorized));
+    }
+
+    let query = query.into_inner();
+    let text = match std::str::from_utf8(&body) {
+        Ok(text) => text,
+        Err(_) => return Err(reject::custom(super::BadRequest)),
+    };
+
+    match crate::import::file::import(
+        &db,
+        text,
+        query.format.unwrap_or_default(),
+        query.dry_run.unwrap_or(false),
+    )
+    .await
+    {
+        Ok(report) => Ok(report.into()),
+        Err(err) => {
+            tracing::error!("{}", err);
+            Err(reject::custom(super::InternalError))
+        }
+    }
+}
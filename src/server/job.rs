@@ -0,0 +1,161 @@
+use crate::entity::{chain, job};
+use rweb::*;
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait,
+    QueryFilter, QueryOrder, QuerySelect, Statement,
+};
+
+pub fn job(model: job::Model) -> shared::Job {
+    shared::Job {
+        id: model.id,
+        chain: model.chain,
+        address: model.address_id,
+        state: shared::JobState::from_i32(model.state),
+        attempts: model.attempts,
+        error: model.error,
+        created: model.created.timestamp(),
+        started: model.started.map(|t| t.timestamp()),
+        finished: model.finished.map(|t| t.timestamp()),
+        not_before: model.not_before.map(|t| t.timestamp()),
+    }
+}
+
+/// Queue addresses of chains whose feed can crawl them, queued or running jobs
+/// of the same address are returned instead of new ones
+pub async fn enqueue(
+    db: &DatabaseConnection,
+    addresses: Vec<i64>,
+) -> Result<Vec<shared::Job>, String> {
+    // Only explorers have per address index
+    let chains = chain::Entity::find()
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|c| {
+            matches!(
+                serde_json::from_value(c.params.clone()),
+                Ok(shared::ChainParam::ArbiScan(_)
+                    | shared::ChainParam::EtherScan(_)
+                    | shared::ChainParam::PolyScan(_))
+            )
+        })
+        .map(|c| c.id)
+        .collect::<Vec<i32>>();
+
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        INSERT INTO job (chain, address_id, state, attempts)
+        SELECT A.chain, A.id, 0, 0
+        FROM address A
+        WHERE A.id = ANY($1) AND A.chain = ANY($2) AND NOT EXISTS (
+            SELECT 1 FROM job J WHERE J.address_id = A.id AND J.state IN (0, 1)
+        );
+        "#,
+        vec![addresses.clone().into(), chains.into()],
+    );
+    db.execute(statement).await.map_err(|e| e.to_string())?;
+
+    job::Entity::find()
+        .filter(job::Column::AddressId.is_in(addresses))
+        .filter(job::Column::State.is_in([
+            shared::JobState::Queued as i32,
+            shared::JobState::Running as i32,
+        ]))
+        .order_by_asc(job::Column::Id)
+        .all(db)
+        .await
+        .map(|list| list.into_iter().map(job).collect())
+        .map_err(|e| e.to_string())
+}
+
+#[post("/api/job/")]
+#[openapi(description = "Queue address processing jobs")]
+pub async fn create(
+    #[data] db: DatabaseConnection,
+    #[data] token: String,
+    #[header = "authorization"] authorization: String,
+    body: Json<shared::JobRequest>,
+) -> Result<Json<Vec<shared::Job>>, Rejection> {
+    if !authorization.ends_with(&token) {
+        return Err(reject::custom(super::Unauthorized));
+    }
+
+    match enqueue(&db, body.into_inner().addresses).await {
+        Ok(list) => Ok(list.into()),
+        Err(err) => {
+            tracing::error!("{}", err);
+            Err(reject::custom(super::InternalError))
+        }
+    }
+}
+
+#[get("/api/job/{id}")]
+#[openapi(description = "Read address processing job")]
+pub async fn detail(
+    #[data] db: DatabaseConnection,
+    id: i64,
+) -> Result<Json<shared::Job>, Rejection> {
+    match job::Entity::find_by_id(id).one(&db).await {
+        Ok(Some(value)) => Ok(job(value).into()),
+        _ => Err(reject::not_found()),
+    }
+}
+
+#[get("/api/job/")]
+#[openapi(description = "Read address processing jobs, newest first")]
+pub async fn list(
+    #[data] db: DatabaseConnection,
+    query: Query<shared::JobQuery>,
+) -> Result<Json<Vec<shared::Job>>, Rejection> {
+    let query = query.into_inner();
+
+    let mut select = job::Entity::find();
+    if let Some(chain) = query.chain {
+        select = select.filter(job::Column::Chain.eq(chain));
+    }
+    if let Some(state) = query.state {
+        select = select.filter(job::Column::State.eq(state as i32));
+    }
+
+    match select
+        .order_by_desc(job::Column::Id)
+        .limit(query.limit.unwrap_or(100).max(0) as u64)
+        .all(&db)
+        .await
+    {
+        Ok(list) => Ok(list.into_iter().map(job).collect::<Vec<_>>().into()),
+        _ => Err(reject::not_found()),
+    }
+}
+
+#[post("/api/job/{id}/cancel")]
+#[openapi(description = "Cancel queued address processing job")]
+pub async fn cancel(
+    #[data] db: DatabaseConnection,
+    #[data] token: String,
+    #[header = "authorization"] authorization: String,
+    id: i64,
+) -> Result<Json<shared::Job>, Rejection> {
+    if !authorization.ends_with(&token) {
+        return Err(reject::custom(super::Unauthorized));
+    }
+
+    // Running job can't be interrupted, claimed job is no longer queued
+    let result = job::Entity::update_many()
+        .col_expr(
+            job::Column::State,
+            Expr::value(shared::JobState::Cancelled as i32),
+        )
+        .filter(job::Column::Id.eq(id))
+        .filter(job::Column::State.eq(shared::JobState::Queued as i32))
+        .exec(&db)
+        .await;
+
+    match (result, job::Entity::find_by_id(id).one(&db).await) {
+        (Ok(result), Ok(Some(value))) if result.rows_affected > 0 => Ok(job(value).into()),
+        (Ok(_), Ok(Some(_))) => Err(reject::custom(super::BadRequest)),
+        _ => Err(reject::not_found()),
+    }
+}
@@ -8,11 +8,17 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 mod address;
 mod analysis;
+mod asset;
+mod block;
 mod chain;
+mod import;
+mod job;
+pub mod native;
 mod service;
 mod tag;
 mod transaction;
 mod transform;
+mod wallet;
 
 #[derive(Debug, Clone)]
 pub struct Unauthorized;
@@ -23,9 +29,13 @@ pub struct NotFound;
 #[derive(Debug, Clone)]
 pub struct InternalError;
 
+#[derive(Debug, Clone)]
+pub struct BadRequest;
+
 impl warp::reject::Reject for Unauthorized {}
 impl warp::reject::Reject for NotFound {}
 impl warp::reject::Reject for InternalError {}
+impl warp::reject::Reject for BadRequest {}
 
 #[get("/api/token")]
 #[openapi(description = "Check token")]
@@ -50,11 +60,15 @@ pub async fn run(
     let (spec, filter) = openapi::spec().build(|| {
         token_check(token.clone())
             .or(tag::create(db.clone(), token.clone()))
+            .or(tag::tree(db.clone()))
+            .or(tag::subtree(db.clone()))
             .or(tag::detail(db.clone()))
             .or(tag::list(db.clone()))
             .or(tag::update(db.clone(), token.clone()))
             .or(tag::delete(db.clone(), token.clone()))
             // Service
+            .or(service::sync(db.clone(), token.clone()))
+            .or(service::mark(db.clone(), token.clone()))
             .or(service::create(db.clone(), token.clone()))
             .or(service::detail(db.clone()))
             .or(service::list(db.clone()))
@@ -68,7 +82,16 @@ pub async fn run(
             ))
             .or(chain::detail(db.clone()))
             .or(chain::list(db.clone()))
-            .or(chain::update(db.clone(), token.clone()))
+            .or(chain::status(feed_channel.clone(), db.clone()))
+            .or(chain::pause(feed_channel.clone(), token.clone()))
+            .or(chain::resume(feed_channel.clone(), token.clone()))
+            .or(chain::rewind(feed_channel.clone(), token.clone()))
+            .or(chain::backfill(feed_channel.clone(), token.clone()))
+            .or(chain::update(
+                db.clone(),
+                feed_channel.clone(),
+                token.clone(),
+            ))
             .or(chain::delete(
                 token.clone(),
                 db.clone(),
@@ -79,15 +102,24 @@ pub async fn run(
             .or(address::update(db.clone(), token.clone()))
             .or(address::delete(db.clone(), token.clone()))
             .or(address::create(db.clone(), token.clone()))
-            .or(address::process(
-                db.clone(),
-                token.clone(),
-                feed_channel.clone(),
-            ))
+            .or(address::process(token.clone(), db.clone()))
             .or(address::list_by_address(db.clone()))
             .or(address::list_by_tag(db.clone()))
             .or(address::list_by_service(db.clone()))
             .or(address::list_by_transaction(db.clone()))
+            // Job
+            .or(job::create(db.clone(), token.clone()))
+            .or(job::detail(db.clone()))
+            .or(job::list(db.clone()))
+            .or(job::cancel(token.clone(), db.clone()))
+            // Wallet
+            .or(wallet::detail(db.clone()))
+            // Import
+            .or(import::upload(db.clone(), token.clone()))
+            // Asset
+            .or(asset::policy(db.clone()))
+            // Block
+            .or(block::detail(db.clone()))
             // Transaction
             .or(transaction::create(db.clone(), token.clone()))
             .or(transaction::detail(db.clone()))
@@ -112,6 +144,11 @@ pub async fn run(
                     )
                 } else if let Some(_err) = err.find::<NotFound>() {
                     ("NOT FOUND".to_string(), warp::http::StatusCode::NOT_FOUND)
+                } else if let Some(_err) = err.find::<BadRequest>() {
+                    (
+                        "BAD REQUEST".to_string(),
+                        warp::http::StatusCode::BAD_REQUEST,
+                    )
                 } else {
                     (
                         "INTERNAL_SERVER_ERROR".to_string(),
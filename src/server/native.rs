@@ -0,0 +1,167 @@
+//! Chain native address forms
+use crate::entity::chain;
+use once_cell::sync::Lazy;
+use sea_orm::{DatabaseConnection, EntityTrait};
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+/// Address forms of the chains, chains change only through the chain endpoints
+static FORMATS: Lazy<RwLock<Option<BTreeMap<i32, Format>>>> = Lazy::new(|| RwLock::new(None));
+
+/// Address form of the chain, hex is used when the chain has no other one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Hex,
+    /// EIP-55 checksummed `0x` prefixed hex
+    Evm,
+    /// Bech32 for Shelley addresses, Byron ones are stored as base58 text
+    Cardano,
+}
+
+impl Format {
+    pub fn from_params(params: &serde_json::Value) -> Self {
+        match serde_json::from_value(params.clone()) {
+            Ok(shared::ChainParam::EtherScan(_))
+            | Ok(shared::ChainParam::PolyScan(_))
+            | Ok(shared::ChainParam::ArbiScan(_))
+            | Ok(shared::ChainParam::EvmRpc(_)) => Format::Evm,
+            Ok(shared::ChainParam::Cardano(_)) => Format::Cardano,
+            _ => Format::Hex,
+        }
+    }
+}
+
+/// Address form of every chain, loaded once until a chain changes
+pub async fn formats(db: &DatabaseConnection) -> BTreeMap<i32, Format> {
+    let cached = FORMATS.read().unwrap().clone();
+    if let Some(formats) = cached {
+        return formats;
+    }
+
+    match chain::Entity::find().all(db).await {
+        Ok(list) => {
+            let formats: BTreeMap<i32, Format> = list
+                .iter()
+                .map(|c| (c.id, Format::from_params(&c.params)))
+                .collect();
+            *FORMATS.write().unwrap() = Some(formats.clone());
+            formats
+        }
+        Err(err) => {
+            tracing::error!("{}", err.to_string());
+            BTreeMap::new()
+        }
+    }
+}
+
+/// Forget the address forms after a chain was created, changed or removed
+pub fn invalidate() {
+    *FORMATS.write().unwrap() = None;
+}
+
+/// EIP-55 checksum, letters are uppercase when the matching nibble of the
+/// keccak hash of the lowercase hex is 8 or more
+fn checksum(bytes: &[u8]) -> String {
+    let lower = hex::encode(bytes);
+    let hash = cryptoxide::hashing::keccak256(lower.as_bytes());
+    let checksummed = lower
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = if i % 2 == 0 {
+                hash[i / 2] >> 4
+            } else {
+                hash[i / 2] & 0x0f
+            };
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect::<String>();
+    format!("0x{}", checksummed)
+}
+
+/// Render stored address bytes in the native form of the chain
+pub fn render(format: Format, bytes: &[u8]) -> String {
+    match format {
+        Format::Hex => hex::encode(bytes),
+        Format::Evm if bytes.len() == 20 => checksum(bytes),
+        Format::Evm => format!("0x{}", hex::encode(bytes)),
+        Format::Cardano => match pallas_addresses::Address::from_bytes(bytes) {
+            Ok(pallas_addresses::Address::Byron(byron)) => byron.to_base58(),
+            Ok(address) => address.to_bech32().unwrap_or_else(|_| hex::encode(bytes)),
+            Err(_) => match std::str::from_utf8(bytes) {
+                Ok(text) if pallas_addresses::ByronAddress::from_base58(text).is_ok() => {
+                    text.to_string()
+                }
+                _ => hex::encode(bytes),
+            },
+        },
+    }
+}
+
+/// Address bytes as the feeds store them from any of the native forms or
+/// hex with optional `0x` prefix. Mixed case EVM address must match its
+/// EIP-55 checksum.
+pub fn parse(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim();
+    let digits = text.trim_start_matches("0x");
+    if let Ok(bytes) = hex::decode(digits) {
+        let mixed = digits.chars().any(|c| c.is_ascii_uppercase())
+            && digits.chars().any(|c| c.is_ascii_lowercase());
+        if mixed && bytes.len() == 20 && checksum(&bytes)[2..] != *digits {
+            return None;
+        }
+        Some(bytes)
+    } else if let Ok(address) = pallas_addresses::Address::from_bech32(text) {
+        Some(address.to_vec())
+    } else if pallas_addresses::ByronAddress::from_base58(text).is_ok() {
+        Some(text.as_bytes().to_vec())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test vectors of EIP-55
+    const CHECKSUMMED: [&str; 8] = [
+        "0x52908400098527886E0F7030069857D2E4169EE7",
+        "0x8617E340B3D01FA5F11F306F4090FD50E238070D",
+        "0xde709f2102306220921060314715629080e2fb77",
+        "0x27b1fdb04752bbc536007a920d24acb045561c26",
+        "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+        "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+        "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+        "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+    ];
+
+    #[test]
+    fn render_checksum() {
+        for address in CHECKSUMMED {
+            let bytes = hex::decode(&address[2..]).unwrap();
+            assert_eq!(render(Format::Evm, &bytes), address);
+        }
+    }
+
+    #[test]
+    fn parse_checksum() {
+        for address in CHECKSUMMED {
+            let bytes = hex::decode(&address[2..]).unwrap();
+            assert_eq!(parse(address), Some(bytes.clone()));
+            assert_eq!(parse(&address.to_lowercase()), Some(bytes.clone()));
+            assert_eq!(parse(&address[2..].to_uppercase()), Some(bytes));
+        }
+    }
+
+    #[test]
+    fn parse_wrong_checksum() {
+        assert_eq!(parse("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD"), None);
+        assert_eq!(parse("0xFb6916095ca1df60bB79Ce92cE3Ea74c37c5d359"), None);
+    }
+}
@@ -2,6 +2,16 @@ use crate::entity::service;
 use rweb::*;
 use sea_orm::{ActiveModelTrait, ActiveValue, DatabaseConnection, EntityTrait, ModelTrait};
 
+fn service_query(value: service::Model) -> shared::Service {
+    shared::Service {
+        id: Some(value.id),
+        title: value.title,
+        description: value.description,
+        url: value.url,
+        tags: value.tags,
+    }
+}
+
 #[post("/api/service/")] // Create address endpoint
 #[openapi(description = "Read address record")]
 pub async fn create(
@@ -20,16 +30,15 @@ pub async fn create(
     let value = service::ActiveModel {
         id: ActiveValue::NotSet,
         title: ActiveValue::Set(body.title.clone()),
+        description: ActiveValue::Set(body.description.clone()),
+        url: ActiveValue::Set(body.url.clone()),
+        tags: ActiveValue::Set(body.tags.clone()),
     }
     .insert(&db)
     .await;
 
     match value {
-        Ok(new) => Ok(shared::Service {
-            id: Some(new.id),
-            title: new.title,
-        }
-        .into()),
+        Ok(new) => Ok(service_query(new).into()),
         _ => Err(reject::not_found()),
     }
 }
@@ -41,11 +50,7 @@ pub async fn detail(
     id: i32,
 ) -> Result<Json<shared::Service>, Rejection> {
     match service::Entity::find_by_id(id).one(&db).await {
-        Ok(Some(value)) => Ok(shared::Service {
-            id: Some(value.id),
-            title: value.title.clone(),
-        }
-        .into()),
+        Ok(Some(value)) => Ok(service_query(value).into()),
         _ => Err(reject::not_found()),
     }
 }
@@ -55,11 +60,8 @@ pub async fn detail(
 pub async fn list(#[data] db: DatabaseConnection) -> Result<Json<Vec<shared::Service>>, Rejection> {
     match service::Entity::find().all(&db).await {
         Ok(service_list) => Ok(service_list
-            .iter()
-            .map(|s| shared::Service {
-                id: Some(s.id),
-                title: s.title.clone(),
-            })
+            .into_iter()
+            .map(service_query)
             .collect::<Vec<shared::Service>>()
             .into()),
         _ => Err(reject::not_found()),
@@ -86,13 +88,12 @@ pub async fn update(
             let mut value: service::ActiveModel = value.into();
 
             value.title = ActiveValue::Set(body.title.clone());
+            value.description = ActiveValue::Set(body.description.clone());
+            value.url = ActiveValue::Set(body.url.clone());
+            value.tags = ActiveValue::Set(body.tags.clone());
             let value: service::Model = value.update(&db).await.unwrap();
 
-            Ok(shared::Service {
-                title: value.title.clone(),
-                id: Some(value.id),
-            }
-            .into())
+            Ok(service_query(value).into())
         }
         _ => Err(reject::not_found()),
     }
@@ -118,3 +119,43 @@ pub async fn delete(
         _ => Err(reject::not_found()),
     }
 }
+
+#[post("/api/service/sync")]
+#[openapi(description = "Sync services from the registry files")]
+pub async fn sync(
+    #[data] db: DatabaseConnection,
+    #[data] token: String,
+    #[header = "authorization"] authorization: String,
+) -> Result<Json<shared::ServiceSync>, Rejection> {
+    if !authorization.ends_with(&token) {
+        return Err(reject::custom(super::Unauthorized));
+    }
+
+    match crate::service::registry::sync(&db, &crate::service::registry::directory()).await {
+        Ok(report) => Ok(report.into()),
+        Err(err) => {
+            tracing::error!("{}", err);
+            Err(reject::custom(super::InternalError))
+        }
+    }
+}
+
+#[post("/api/service/mark")]
+#[openapi(description = "Mark all addresses by every service")]
+pub async fn mark(
+    #[data] db: DatabaseConnection,
+    #[data] token: String,
+    #[header = "authorization"] authorization: String,
+) -> Result<Json<Vec<shared::ServiceMark>>, Rejection> {
+    if !authorization.ends_with(&token) {
+        return Err(reject::custom(super::Unauthorized));
+    }
+
+    match crate::service::registry::remark(&db).await {
+        Ok(marks) => Ok(marks.into()),
+        Err(err) => {
+            tracing::error!("{}", err);
+            Err(reject::custom(super::InternalError))
+        }
+    }
+}
@@ -1,6 +1,71 @@
 use crate::entity::tag;
 use rweb::*;
 use sea_orm::{ActiveModelTrait, ActiveValue, DatabaseConnection, EntityTrait, ModelTrait};
+use std::collections::BTreeMap;
+
+fn tag_query(value: &tag::Model) -> shared::Tag {
+    shared::Tag {
+        id: Some(value.id),
+        title: value.title.clone(),
+        parent: value.parent,
+        category: shared::TagCategory::from_i32(value.category),
+    }
+}
+
+/// Parent must exist and must not be the tag or one of its descendants
+async fn valid_parent(db: &DatabaseConnection, id: Option<i32>, parent: Option<i32>) -> bool {
+    let parent = match parent {
+        Some(parent) => parent,
+        None => return true,
+    };
+    if !matches!(tag::Entity::find_by_id(parent).one(db).await, Ok(Some(_))) {
+        return false;
+    }
+    match id {
+        Some(id) => match crate::tag::descendants(db, &[id]).await {
+            Ok(descendants) => !descendants.contains(&parent),
+            Err(err) => {
+                tracing::error!("{}", err);
+                false
+            }
+        },
+        None => true,
+    }
+}
+
+/// Tag with its subtree, `children` maps parent to its child tags
+fn tag_node(
+    value: &tag::Model,
+    children: &BTreeMap<Option<i32>, Vec<tag::Model>>,
+) -> shared::TagNode {
+    shared::TagNode {
+        tag: tag_query(value),
+        children: children
+            .get(&Some(value.id))
+            .iter()
+            .flat_map(|list| list.iter())
+            .map(|child| tag_node(child, children))
+            .collect(),
+    }
+}
+
+async fn tag_children(
+    db: &DatabaseConnection,
+) -> Result<BTreeMap<Option<i32>, Vec<tag::Model>>, Rejection> {
+    match tag::Entity::find().all(db).await {
+        Ok(tag_list) => {
+            let mut children: BTreeMap<Option<i32>, Vec<tag::Model>> = BTreeMap::new();
+            for value in tag_list {
+                children.entry(value.parent).or_default().push(value);
+            }
+            Ok(children)
+        }
+        Err(err) => {
+            tracing::error!("{}", err);
+            Err(reject::custom(super::InternalError))
+        }
+    }
+}
 
 #[post("/api/tag/")] // Create address endpoint
 #[openapi(description = "Read address record")]
@@ -15,24 +80,53 @@ pub async fn create(
     }
 
     let body = body.into_inner();
+    if !valid_parent(&db, None, body.parent).await {
+        return Err(reject::custom(super::BadRequest));
+    }
 
     let value = tag::ActiveModel {
         id: ActiveValue::NotSet,
         title: ActiveValue::Set(body.title.clone()),
+        parent: ActiveValue::Set(body.parent),
+        category: ActiveValue::Set(body.category as i32),
     }
     .insert(&db)
     .await;
 
     match value {
-        Ok(new) => Ok(shared::Tag {
-            id: Some(new.id),
-            title: new.title,
-        }
-        .into()),
+        Ok(new) => Ok(tag_query(&new).into()),
         _ => Err(warp::reject::custom(super::InternalError)),
     }
 }
 
+#[get("/api/tag/tree")]
+#[openapi(description = "Read tag tree from the root tags")]
+pub async fn tree(#[data] db: DatabaseConnection) -> Result<Json<Vec<shared::TagNode>>, Rejection> {
+    let children = tag_children(&db).await?;
+
+    Ok(children
+        .get(&None)
+        .iter()
+        .flat_map(|list| list.iter())
+        .map(|root| tag_node(root, &children))
+        .collect::<Vec<shared::TagNode>>()
+        .into())
+}
+
+#[get("/api/tag/tree/{id}")]
+#[openapi(description = "Read tag subtree")]
+pub async fn subtree(
+    #[data] db: DatabaseConnection,
+    id: i32,
+) -> Result<Json<shared::TagNode>, Rejection> {
+    let children = tag_children(&db).await?;
+
+    match children.values().flatten().find(|t| t.id == id) {
+        Some(value) => Ok(tag_node(value, &children).into()),
+        None => Err(reject::not_found()),
+    }
+}
+
 #[get("/api/tag/{id}")] // Create address endpoint
 #[openapi(description = "Read address record")]
 pub async fn detail(
@@ -40,11 +134,7 @@ pub async fn detail(
     id: i32,
 ) -> Result<Json<shared::Tag>, Rejection> {
     match tag::Entity::find_by_id(id).one(&db).await {
-        Ok(Some(value)) => Ok(shared::Tag {
-            id: Some(value.id),
-            title: value.title.clone(),
-        }
-        .into()),
+        Ok(Some(value)) => Ok(tag_query(&value).into()),
         _ => Err(warp::reject::not_found()),
     }
 }
@@ -55,10 +145,7 @@ pub async fn list(#[data] db: DatabaseConnection) -> Result<Json<Vec<shared::Tag
     match tag::Entity::find().all(&db).await {
         Ok(chain_list) => Ok(chain_list
             .iter()
-            .map(|t| shared::Tag {
-                id: Some(t.id),
-                title: t.title.clone(),
-            })
+            .map(tag_query)
             .collect::<Vec<shared::Tag>>()
             .into()),
         _ => Err(reject::not_found()),
@@ -83,16 +170,18 @@ pub async fn update(
 
     match tag::Entity::find_by_id(id).one(&db).await {
         Ok(Some(value)) => {
+            if !valid_parent(&db, Some(id), body.parent).await {
+                return Err(reject::custom(super::BadRequest));
+            }
+
             let mut value: tag::ActiveModel = value.into();
 
             value.title = ActiveValue::Set(body.title.clone());
+            value.parent = ActiveValue::Set(body.parent);
+            value.category = ActiveValue::Set(body.category as i32);
             let value: tag::Model = value.update(&db).await.unwrap();
 
-            Ok(shared::Tag {
-                title: value.title.clone(),
-                id: Some(value.id),
-            }
-            .into())
+            Ok(tag_query(&value).into())
         }
         _ => Err(reject::not_found()),
     }
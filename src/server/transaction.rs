@@ -1,4 +1,5 @@
 use crate::entity::transaction;
+use crate::server::transform;
 use rweb::*;
 use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, EntityTrait, Statement};
 
@@ -18,15 +19,22 @@ pub async fn detail(
     #[data] db: DatabaseConnection,
     id: String,
 ) -> Result<Json<shared::Transaction>, Rejection> {
-    Ok(shared::Transaction {
-        id: Some(1),
-        chain: 1,
-        amount: 0,
-        from: vec![1],
-        to: vec![1],
-        hash: vec![0],
+    let id = id.parse::<i64>().map_err(|_| reject::not_found())?;
+    match transaction::Entity::find_by_id(id).one(&db).await {
+        Ok(Some(t)) => Ok(shared::Transaction {
+            id: Some(t.id),
+            chain: t.chain,
+            hash: t.hash,
+            from: t.from,
+            to: t.to,
+            block: t.block_id,
+            timestamp: t.timestamp.map(|t| t.timestamp()),
+            position: t.position,
+            transfers: transform::map_transfers(&db, t.id).await,
+        }
+        .into()),
+        _ => Err(reject::not_found()),
     }
-    .into())
 }
 
 #[post("/api/transaction/{id}")] // Create address endpoint
@@ -40,10 +48,10 @@ pub async fn update(
     Ok(shared::Transaction {
         id: Some(1),
         chain: 1,
-        amount: 0,
         from: vec![1],
         to: vec![1],
         hash: vec![0],
+        ..Default::default()
     }
     .into())
 }
@@ -58,10 +66,10 @@ pub async fn delete(
     Ok(shared::Transaction {
         id: Some(1),
         chain: 1,
-        amount: 0,
         from: vec![1],
         to: vec![1],
         hash: vec![0],
+        ..Default::default()
     }
     .into())
 }
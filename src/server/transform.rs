@@ -1,10 +1,10 @@
-use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, Statement};
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, QueryResult, Statement};
 use std::collections::{BTreeMap, BTreeSet};
 
 fn map_address_query(address_list: &BTreeSet<i64>) -> Statement {
     Statement::from_sql_and_values(
         DbBackend::Postgres,
-        r#"SELECT id, chain, title, hash, tags, services FROM address WHERE id = ANY($1);"#,
+        r#"SELECT id, chain, title, hash, tags, services, wallet_id, kind FROM address WHERE id = ANY($1);"#,
         vec![address_list
             .iter()
             .map(|a| a.clone())
@@ -13,25 +13,141 @@ fn map_address_query(address_list: &BTreeSet<i64>) -> Statement {
     )
 }
 
+/// Asset selected as `asset`, `chain`, `kind`, `contract` and `name` columns
+pub fn asset(row: &QueryResult) -> shared::Asset {
+    shared::Asset {
+        id: row.try_get("", "asset").unwrap(),
+        chain: row.try_get("", "chain").unwrap(),
+        kind: shared::AssetKind::from_i32(row.try_get("", "kind").unwrap()),
+        contract: hex::encode(row.try_get::<Vec<u8>>("", "contract").unwrap()),
+        name: hex::encode(row.try_get::<Vec<u8>>("", "name").unwrap()),
+    }
+}
+
+/// Transfers of the transaction, inputs first
+pub async fn map_transfers(db: &DatabaseConnection, transaction_id: i64) -> Vec<shared::Transfer> {
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        SELECT
+            X.address_id, X.output, X.index, X.amount::text AS amount, X.internal,
+            A.id AS asset, A.chain, A.kind, A.contract, A.name
+        FROM transfer X JOIN asset A ON A.id = X.asset_id
+        WHERE X.transaction_id = $1
+        ORDER BY X.output, X.index, A.id;
+        "#,
+        vec![transaction_id.into()],
+    );
+
+    match db.query_all(statement).await {
+        Ok(query) => query
+            .iter()
+            .map(|row| shared::Transfer {
+                address: row.try_get("", "address_id").unwrap(),
+                asset: asset(row),
+                output: row.try_get("", "output").unwrap(),
+                index: row.try_get("", "index").unwrap(),
+                amount: row.try_get("", "amount").unwrap(),
+                internal: row.try_get("", "internal").unwrap(),
+            })
+            .collect(),
+        Err(err) => {
+            tracing::error!("{}", err.to_string());
+            Vec::new()
+        }
+    }
+}
+
+/// Value sent by counterparties to the addresses (inputs) and received from them
+/// (outputs), in the wallet mode counterparties of one wallet are summed together
+/// under the address with the lowest ID
+pub async fn map_amounts(
+    db: &DatabaseConnection,
+    addresses: &[i64],
+    mode: shared::RelationMode,
+    inputs: &mut BTreeMap<i64, Vec<shared::Amount>>,
+    outputs: &mut BTreeMap<i64, Vec<shared::Amount>>,
+) {
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        SELECT
+            G.id AS address_id, X.output, sum(X.amount)::text AS amount,
+            A.id AS asset, A.chain, A.kind, A.contract, A.name
+        FROM transfer X
+            JOIN asset A ON A.id = X.asset_id
+            JOIN address C ON C.id = X.address_id
+            CROSS JOIN LATERAL (
+                SELECT CASE
+                    WHEN $2 AND C.wallet_id IS NOT NULL
+                        THEN (SELECT min(B.id) FROM address B WHERE B.wallet_id = C.wallet_id)
+                    ELSE C.id
+                END AS id
+            ) G
+        WHERE X.transaction_id IN (SELECT transaction_id FROM transfer WHERE address_id = ANY($1))
+            AND NOT X.address_id = ANY($1)
+            AND EXISTS (
+                SELECT 1 FROM transfer Y
+                WHERE Y.transaction_id = X.transaction_id
+                    AND Y.address_id = ANY($1)
+                    AND Y.output != X.output
+            )
+        GROUP BY G.id, X.output, A.id;
+        "#,
+        vec![
+            addresses.to_vec().into(),
+            (mode == shared::RelationMode::Wallet).into(),
+        ],
+    );
+
+    match db.query_all(statement).await {
+        Ok(query) => {
+            for row in query.iter() {
+                let address: i64 = row.try_get("", "address_id").unwrap();
+                let amount = shared::Amount {
+                    asset: asset(row),
+                    amount: row.try_get("", "amount").unwrap(),
+                };
+                if row.try_get("", "output").unwrap() {
+                    outputs.entry(address).or_default().push(amount);
+                } else {
+                    inputs.entry(address).or_default().push(amount);
+                }
+            }
+        }
+        Err(err) => tracing::error!("{}", err.to_string()),
+    }
+}
+
 pub async fn map_addresses(
     db: &DatabaseConnection,
     address_list: &mut BTreeSet<i64>,
     address_map: &mut BTreeMap<i64, shared::PrivAddress>,
 ) {
     let statement = map_address_query(address_list);
+    let formats = super::native::formats(db).await;
 
     if let Ok(query) = db.query_all(statement.clone()).await {
         for row in query.iter() {
             let id: i64 = row.try_get("", "id").unwrap();
-            let hash = row.try_get("", "hash").unwrap();
+            let hash: Vec<u8> = row.try_get("", "hash").unwrap();
+            let chain: i32 = row.try_get("", "chain").unwrap();
             address_map.insert(
                 id,
                 shared::PrivAddress {
                     title: row.try_get("", "title").unwrap_or(String::new()),
-                    chain: row.try_get("", "chain").unwrap(),
+                    chain,
+                    native: super::native::render(
+                        formats.get(&chain).cloned().unwrap_or_default(),
+                        &hash,
+                    ),
                     hash: hash,
                     tags: row.try_get("", "tags").unwrap_or(Vec::new()),
                     services: row.try_get("", "services").unwrap_or(Vec::new()),
+                    wallet: row.try_get("", "wallet_id").unwrap_or_default(),
+                    kind: shared::AddressKind::from_i32(
+                        row.try_get("", "kind").unwrap_or_default(),
+                    ),
                 },
             );
         }
@@ -46,6 +162,7 @@ pub async fn map_addresses_extended(
     service_list: &mut BTreeSet<i32>,
 ) {
     let statement = map_address_query(address_list);
+    let formats = super::native::formats(db).await;
 
     if let Ok(query) = db.query_all(statement.clone()).await {
         for row in query.iter() {
@@ -61,15 +178,23 @@ pub async fn map_addresses_extended(
                 service_list.insert(*service);
             }
 
-            let hash = row.try_get("", "hash").unwrap();
+            let hash: Vec<u8> = row.try_get("", "hash").unwrap();
+            let chain: i32 = row.try_get("", "chain").unwrap();
+            let native =
+                super::native::render(formats.get(&chain).cloned().unwrap_or_default(), &hash);
             address_map.insert(
                 id,
                 shared::PrivAddress {
-                    title: row.try_get("", "title").unwrap_or(hex::encode(&hash)),
-                    chain: row.try_get("", "chain").unwrap(),
+                    title: row.try_get("", "title").unwrap_or(native.clone()),
+                    chain,
+                    native,
                     hash: hash,
                     tags: tags,
                     services: services,
+                    wallet: row.try_get("", "wallet_id").unwrap_or_default(),
+                    kind: shared::AddressKind::from_i32(
+                        row.try_get("", "kind").unwrap_or_default(),
+                    ),
                 },
             );
         }
@@ -133,6 +258,8 @@ pub fn address_ref_human(
     tag_map: &BTreeMap<i32, String>,
     service_map: &BTreeMap<i32, String>,
     addresses: BTreeMap<i64, i32>,
+    amounts: &BTreeMap<i64, Vec<shared::Amount>>,
+    internal: &BTreeMap<i64, i32>,
 ) -> Vec<shared::AddressRefHuman> {
     let mut result: Vec<shared::AddressRefHuman> = addresses
         .iter()
@@ -141,8 +268,13 @@ pub fn address_ref_human(
                 return shared::AddressRefHuman {
                     id: address_id.clone(),
                     hex: hex::encode(&address.hash),
+                    native: address.native.clone(),
                     human: address.title.clone(),
                     quantity: address_count.clone(),
+                    wallet: address.wallet,
+                    kind: address.kind,
+                    internal: internal.get(address_id).cloned().unwrap_or_default(),
+                    amounts: amounts.get(address_id).cloned().unwrap_or_default(),
                     tags: address
                         .tags
                         .iter()
@@ -165,6 +297,8 @@ pub fn address_ref_human(
 pub fn address_ref(
     address_map: &BTreeMap<i64, shared::PrivAddress>,
     addresses: BTreeMap<i64, i32>,
+    amounts: &BTreeMap<i64, Vec<shared::Amount>>,
+    internal: &BTreeMap<i64, i32>,
 ) -> Vec<shared::AddressRef> {
     addresses
         .iter()
@@ -173,8 +307,13 @@ pub fn address_ref(
                 return shared::AddressRef {
                     id: address_id.clone(),
                     hex: hex::encode(&address.hash),
+                    native: address.native.clone(),
                     human: address.title.clone(),
                     quantity: address_count.clone(),
+                    wallet: address.wallet,
+                    kind: address.kind,
+                    internal: internal.get(address_id).cloned().unwrap_or_default(),
+                    amounts: amounts.get(address_id).cloned().unwrap_or_default(),
                     tags: address.tags.clone(),
                     services: address.services.clone(),
                 };
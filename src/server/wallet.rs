@@ -0,0 +1,37 @@
+use crate::entity::{address, wallet};
+use rweb::*;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder};
+
+#[get("/api/wallet/{id}")]
+#[openapi(description = "Read wallet with its addresses")]
+pub async fn detail(
+    #[data] db: DatabaseConnection,
+    id: i64,
+) -> Result<Json<shared::Wallet>, Rejection> {
+    let value = match wallet::Entity::find_by_id(id).one(&db).await {
+        Ok(Some(value)) => value,
+        _ => return Err(reject::not_found()),
+    };
+
+    match address::Entity::find()
+        .filter(address::Column::WalletId.eq(value.id))
+        .order_by_asc(address::Column::Id)
+        .all(&db)
+        .await
+    {
+        Ok(list) => {
+            let formats = super::native::formats(&db).await;
+            Ok(shared::Wallet {
+                id: value.id,
+                chain: value.chain,
+                stake: hex::encode(value.stake),
+                addresses: super::address::address_list_query(list, &formats),
+            }
+            .into())
+        }
+        Err(err) => {
+            tracing::error!("{}", err);
+            Err(reject::custom(super::InternalError))
+        }
+    }
+}
@@ -2,7 +2,8 @@ use async_trait::async_trait;
 use sea_orm::prelude::*;
 
 #[async_trait]
-pub trait Service {
-    /// Mark address related with this service with service and tags flags
-    async fn mark_addresses(&self, db: DatabaseConnection) -> Result<(), String>;
+pub trait Service: Send + Sync {
+    /// Mark address related with this service with service and tags flags,
+    /// reports how many addresses were touched
+    async fn mark_addresses(&self, db: DatabaseConnection) -> Result<shared::ServiceMark, String>;
 }
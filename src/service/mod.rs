@@ -2,7 +2,7 @@ use sea_orm::{prelude::*, DatabaseConnection};
 use sea_query::value::with_array::NotU8;
 use serde::{Deserialize, Serialize};
 pub mod common;
-pub mod dex;
+pub mod registry;
 
 #[derive(Debug, Clone, Eq, PartialEq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
 #[sea_orm(rs_type = "i32", db_type = "Integer")]
@@ -13,10 +13,15 @@ pub enum Service {
 impl NotU8 for Service {}
 //impl TryGetableFromJson for Service {}
 
+/// Services of the service table, the registry files are synced to it
 pub async fn init_services(db: &DatabaseConnection) -> Vec<Box<dyn common::Service>> {
-    vec![
-        Box::new(dex::WingRiders::init(db, 1).await),
-        Box::new(dex::SundaeSwap::init(db, 2).await),
-        Box::new(dex::MinSwap::init(db, 3).await),
-    ]
+    let mut services: Vec<Box<dyn common::Service>> = Vec::new();
+    for service in crate::entity::service::Entity::find()
+        .all(db)
+        .await
+        .unwrap_or_default()
+    {
+        services.push(Box::new(registry::Registered::new(service)));
+    }
+    services
 }
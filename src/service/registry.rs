@@ -0,0 +1,544 @@
+//! Services defined in TOML, YAML or JSON files, synced into the service table
+use crate::entity::service;
+use crate::service::common::Service;
+use crate::tag::Tag;
+use async_trait::async_trait;
+use sea_orm::{prelude::*, ConnectionTrait, DbBackend, Statement, TransactionTrait};
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// How the service hash marks addresses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Script hash or address of the service scripts
+    Address = 0,
+    /// Script hash of the liquidity pools
+    Pool = 1,
+    /// Other script hash of the service, only service tags are added
+    Script = 2,
+    /// Contract address, only the same address is marked
+    Contract = 3,
+}
+
+impl Role {
+    pub const ALL: [Role; 4] = [Role::Address, Role::Pool, Role::Script, Role::Contract];
+
+    /// Tag added to the addresses marked by the hash of this role
+    pub fn tag(&self) -> Option<Tag> {
+        match self {
+            Role::Address => Some(Tag::Address),
+            Role::Pool => Some(Tag::Pool),
+            Role::Script | Role::Contract => None,
+        }
+    }
+}
+
+/// Service as the registry file defines it, hashes are hex with optional `0x`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Definition {
+    pub id: i32,
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Chain IDs the hashes belong to
+    pub chains: Vec<i32>,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    #[serde(default)]
+    pub pools: Vec<String>,
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    #[serde(default)]
+    pub script_hashes: Vec<String>,
+    #[serde(default)]
+    pub contracts: Vec<String>,
+}
+
+impl Definition {
+    fn hashes(&self) -> Result<Vec<(Role, Vec<u8>)>, String> {
+        [
+            (Role::Address, &self.addresses),
+            (Role::Pool, &self.pools),
+            (Role::Script, &self.script_hashes),
+            (Role::Contract, &self.contracts),
+        ]
+        .iter()
+        .flat_map(|(role, list)| list.iter().map(move |hash| (*role, hash)))
+        .map(|(role, hash)| {
+            hex::decode(hash.trim_start_matches("0x"))
+                .map(|bytes| (role, bytes))
+                .map_err(|_| format!("Service {}: invalid hash {}", self.id, hash))
+        })
+        .collect()
+    }
+}
+
+/// File holds one service or a list of them under `services`
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum File {
+    List { services: Vec<Definition> },
+    One(Definition),
+}
+
+/// Registry directory, `SERVICES` variable or `./services`
+pub fn directory() -> PathBuf {
+    PathBuf::from(std::env::var("SERVICES").unwrap_or(String::from("./services")))
+}
+
+fn yaml_to_json(yaml: yaml_rust::Yaml) -> serde_json::Value {
+    match yaml {
+        yaml_rust::Yaml::Real(real) => real
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::String(real)),
+        yaml_rust::Yaml::Integer(integer) => serde_json::Value::from(integer),
+        yaml_rust::Yaml::String(string) => serde_json::Value::String(string),
+        yaml_rust::Yaml::Boolean(boolean) => serde_json::Value::Bool(boolean),
+        yaml_rust::Yaml::Array(array) => {
+            serde_json::Value::Array(array.into_iter().map(yaml_to_json).collect())
+        }
+        yaml_rust::Yaml::Hash(hash) => serde_json::Value::Object(
+            hash.into_iter()
+                .filter_map(|(key, value)| match key {
+                    yaml_rust::Yaml::String(key) => Some((key, yaml_to_json(value))),
+                    yaml_rust::Yaml::Integer(key) => Some((key.to_string(), yaml_to_json(value))),
+                    _ => None,
+                })
+                .collect(),
+        ),
+        _ => serde_json::Value::Null,
+    }
+}
+
+/// Services of the registry file, format is given by the extension
+pub fn parse(path: &Path, text: &str) -> Result<Vec<Definition>, String> {
+    let file: File = match path.extension().and_then(|e| e.to_str()) {
+        Some("toml") => toml::from_str(text).map_err(|e| e.to_string())?,
+        Some("yaml") | Some("yml") => {
+            let document = yaml_rust::YamlLoader::load_from_str(text)
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .next()
+                .ok_or_else(|| String::from("Empty document"))?;
+            serde_json::from_value(yaml_to_json(document)).map_err(|e| e.to_string())?
+        }
+        Some("json") => serde_json::from_str(text).map_err(|e| e.to_string())?,
+        _ => return Err(String::from("Unknown format")),
+    };
+
+    Ok(match file {
+        File::List { services } => services,
+        File::One(definition) => vec![definition],
+    })
+}
+
+/// Store the service and replace its hashes, returns the number of hashes
+async fn store(
+    db: &DatabaseConnection,
+    definition: &Definition,
+    chains: &BTreeSet<i32>,
+) -> Result<u64, String> {
+    if let Some(chain) = definition.chains.iter().find(|c| !chains.contains(c)) {
+        return Err(format!(
+            "Service {}: unknown chain {}",
+            definition.id, chain
+        ));
+    }
+    let hashes = definition.hashes()?;
+
+    let txn = db.begin().await.map_err(|e| e.to_string())?;
+    txn.execute(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        INSERT INTO service (id, title, description, url, tags)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (id) DO UPDATE
+            SET title = EXCLUDED.title, description = EXCLUDED.description,
+                url = EXCLUDED.url, tags = EXCLUDED.tags
+        "#,
+        vec![
+            definition.id.into(),
+            definition.title.clone().into(),
+            definition.description.clone().into(),
+            definition.url.clone().into(),
+            definition
+                .tags
+                .iter()
+                .map(|t| t.to_value())
+                .collect::<Vec<i32>>()
+                .into(),
+        ],
+    ))
+    .await
+    .map_err(|e| e.to_string())?;
+
+    txn.execute(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"DELETE FROM service_hash WHERE service = $1"#,
+        vec![definition.id.into()],
+    ))
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let result = txn
+        .execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"
+            INSERT INTO service_hash (service, chain, role, hash)
+            SELECT $1, C.chain, H.role, H.hash
+            FROM
+                unnest($2::integer[]) AS C(chain)
+                CROSS JOIN unnest($3::integer[], $4::bytea[]) AS H(role, hash)
+            ON CONFLICT DO NOTHING
+            "#,
+            vec![
+                definition.id.into(),
+                definition.chains.clone().into(),
+                hashes
+                    .iter()
+                    .map(|(role, _)| *role as i32)
+                    .collect::<Vec<i32>>()
+                    .into(),
+                hashes
+                    .into_iter()
+                    .map(|(_, hash)| hash)
+                    .collect::<Vec<Vec<u8>>>()
+                    .into(),
+            ],
+        ))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    txn.commit().await.map_err(|e| e.to_string())?;
+    Ok(result.rows_affected())
+}
+
+/// Sync every registry file of the directory into the service table, services
+/// created through the API are kept
+pub async fn sync(
+    db: &DatabaseConnection,
+    directory: &Path,
+) -> Result<shared::ServiceSync, String> {
+    let chains = crate::entity::chain::Entity::find()
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|c| c.id)
+        .collect::<BTreeSet<i32>>();
+
+    let mut paths = std::fs::read_dir(directory)
+        .map_err(|e| format!("{}: {}", directory.display(), e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            matches!(
+                path.extension().and_then(|e| e.to_str()),
+                Some("toml") | Some("yaml") | Some("yml") | Some("json")
+            )
+        })
+        .collect::<Vec<PathBuf>>();
+    paths.sort();
+
+    let mut report = shared::ServiceSync::default();
+    for path in paths {
+        report.files += 1;
+        let definitions = match std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| parse(&path, &text))
+        {
+            Ok(definitions) => definitions,
+            Err(err) => {
+                report.errors.push(format!("{}: {}", path.display(), err));
+                continue;
+            }
+        };
+
+        for definition in definitions.iter() {
+            match store(db, definition, &chains).await {
+                Ok(hashes) => {
+                    report.services += 1;
+                    report.hashes += hashes;
+                }
+                Err(err) => report.errors.push(format!("{}: {}", path.display(), err)),
+            }
+        }
+    }
+
+    // Services created later through the API continue after the registry IDs
+    db.execute(Statement::from_string(
+        DbBackend::Postgres,
+        String::from(
+            r#"SELECT setval(pg_get_serial_sequence('service', 'id'), GREATEST((SELECT MAX(id) FROM service), 1))"#,
+        ),
+    ))
+    .await
+    .map_err(|e| e.to_string())?;
+
+    tracing::info!(
+        "Service registry: {} files, {} services, {} hashes, {} errors",
+        report.files,
+        report.services,
+        report.hashes,
+        report.errors.len()
+    );
+    Ok(report)
+}
+
+/// Command line entry `services [directory]`
+pub async fn command(db: &DatabaseConnection, args: &[String]) -> Result<(), String> {
+    let directory = args.first().map(PathBuf::from).unwrap_or_else(directory);
+    let report = sync(db, &directory).await?;
+    for error in report.errors.iter() {
+        tracing::error!("{}", error);
+    }
+    Ok(())
+}
+
+/// Add services and tags of the matching service hashes to the addresses
+/// selected by the filter, which continues with `$4`. Script hash follows the
+/// header byte of the address, contract hash is the whole address.
+fn mark_statement(filter: &str, mut values: Vec<sea_orm::Value>) -> Statement {
+    let (roles, tags): (Vec<i32>, Vec<i32>) = Role::ALL
+        .iter()
+        .filter_map(|role| role.tag().map(|tag| (*role as i32, tag.to_value())))
+        .unzip();
+    let mut params = vec![roles.into(), tags.into(), (Role::Contract as i32).into()];
+    params.append(&mut values);
+
+    Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        &format!(
+            r#"
+            WITH
+                matches AS (
+                    SELECT
+                        A.id, H.service,
+                        S.tags || ARRAY(
+                            SELECT R.tag
+                            FROM unnest($1::integer[], $2::integer[]) AS R(role, tag)
+                            WHERE R.role = H.role
+                        ) AS tags
+                    FROM
+                        address A
+                        JOIN service_hash H ON H.chain = A.chain AND (
+                            (H.role = $3 AND A.hash = H.hash)
+                            OR (H.role <> $3 AND position(H.hash IN A.hash) = ANY(ARRAY[1, 2]))
+                        )
+                        JOIN service S ON S.id = H.service
+                    WHERE {}
+                ),
+                merged AS (
+                    SELECT
+                        M.id,
+                        array_agg(DISTINCT M.service) AS services,
+                        COALESCE(
+                            array_agg(DISTINCT T.tag) FILTER (WHERE T.tag IS NOT NULL),
+                            ARRAY[]::integer[]
+                        ) AS tags
+                    FROM
+                        matches M
+                        LEFT JOIN LATERAL unnest(M.tags) AS T(tag) ON true
+                    GROUP BY M.id
+                )
+            UPDATE address A
+            SET
+                services = ARRAY(
+                    SELECT DISTINCT V FROM unnest(A.services || merged.services) AS U(V) ORDER BY V
+                ),
+                tags = ARRAY(SELECT DISTINCT V FROM unnest(A.tags || merged.tags) AS U(V) ORDER BY V)
+            FROM merged
+            WHERE A.id = merged.id
+            "#,
+            filter
+        ),
+        params,
+    )
+}
+
+/// Mark new addresses of the chain by all service hashes, feeds call it for
+/// every address they store
+pub async fn mark(
+    db: &DatabaseConnection,
+    chain_id: i32,
+    address_list: Vec<Vec<u8>>,
+) -> Result<u64, String> {
+    if address_list.is_empty() {
+        return Ok(0);
+    }
+
+    db.execute(mark_statement(
+        "A.chain = $4 AND A.hash = ANY($5)",
+        vec![chain_id.into(), address_list.into()],
+    ))
+    .await
+    .map(|result| result.rows_affected())
+    .map_err(|e| e.to_string())
+}
+
+/// Service stored in the service table, its hashes mark the addresses
+#[derive(Clone, Debug)]
+pub struct Registered {
+    id: i32,
+    title: String,
+}
+
+impl Registered {
+    pub fn new(service: service::Model) -> Self {
+        Self {
+            id: service.id,
+            title: service.title,
+        }
+    }
+}
+
+#[async_trait]
+impl Service for Registered {
+    async fn mark_addresses(&self, db: DatabaseConnection) -> Result<shared::ServiceMark, String> {
+        let addresses = db
+            .execute(mark_statement("H.service = $4", vec![self.id.into()]))
+            .await
+            .map_err(|e| e.to_string())?
+            .rows_affected();
+
+        Ok(shared::ServiceMark {
+            service: self.id,
+            title: self.title.clone(),
+            addresses,
+        })
+    }
+}
+
+/// Mark all stored addresses by every service, marks are only added so
+/// services assigned by hand stay
+pub async fn remark(db: &DatabaseConnection) -> Result<Vec<shared::ServiceMark>, String> {
+    let mut marks = Vec::new();
+    for service in super::init_services(db).await.iter() {
+        let mark = service.mark_addresses(db.clone()).await?;
+        tracing::info!("Service {} marked {} addresses", mark.title, mark.addresses);
+        marks.push(mark);
+    }
+    Ok(marks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(text: &str) -> serde_json::Value {
+        let document = yaml_rust::YamlLoader::load_from_str(text).unwrap();
+        yaml_to_json(document[0].clone())
+    }
+
+    #[test]
+    fn yaml_numbers() {
+        assert_eq!(yaml("id: 12"), serde_json::json!({"id": 12}));
+        assert_eq!(yaml("id: 0x1f"), serde_json::json!({"id": 31}));
+        assert_eq!(yaml("id: -3"), serde_json::json!({"id": -3}));
+        assert_eq!(yaml("rate: 1.50"), serde_json::json!({"rate": 1.5}));
+        assert_eq!(yaml("rate: 1e3"), serde_json::json!({"rate": 1000.0}));
+        // Integer out of the i64 range is read as float
+        assert_eq!(
+            yaml("id: 18446744073709551616"),
+            serde_json::json!({"id": 18446744073709551616.0})
+        );
+        // Float without JSON form keeps its text
+        assert_eq!(yaml("rate: .nan"), serde_json::json!({"rate": ".nan"}));
+    }
+
+    #[test]
+    fn yaml_structure() {
+        assert_eq!(
+            yaml("1: one\ntwo: [true, ~, 'text']\n"),
+            serde_json::json!({"1": "one", "two": [true, null, "text"]})
+        );
+        // Long hex doesn't fit an integer and stays a string
+        assert_eq!(
+            yaml("contract: 0x52908400098527886E0F7030069857D2E4169EE7"),
+            serde_json::json!({"contract": "0x52908400098527886E0F7030069857D2E4169EE7"})
+        );
+    }
+
+    #[test]
+    fn parse_formats() {
+        let toml = r#"
+            id = 3
+            title = "MinSwap"
+            chains = [1]
+            tags = ["Dex", "Finance"]
+            pools = ["57c8e718"]
+        "#;
+        let yaml = r#"
+services:
+  - id: 3
+    title: MinSwap
+    chains: [1]
+    tags: [Dex, Finance]
+    pools: ["57c8e718"]
+  - id: 4
+    title: Other
+    chains: [1, 2]
+    contracts: ["0x5290"]
+"#;
+        let json = r#"{"id": 3, "title": "MinSwap", "chains": [1], "tags": ["Dex", "Finance"], "pools": ["57c8e718"]}"#;
+
+        for (name, text) in [
+            ("minswap.toml", toml),
+            ("minswap.yaml", yaml),
+            ("minswap.yml", yaml),
+            ("minswap.json", json),
+        ] {
+            let services = parse(Path::new(name), text).unwrap();
+            assert_eq!(services[0].id, 3, "{}", name);
+            assert_eq!(services[0].title, "MinSwap", "{}", name);
+            assert_eq!(services[0].chains, vec![1], "{}", name);
+            assert_eq!(services[0].tags, vec![Tag::Dex, Tag::Finance], "{}", name);
+            assert_eq!(
+                services[0].hashes().unwrap(),
+                vec![(Role::Pool, vec![0x57, 0xc8, 0xe7, 0x18])],
+                "{}",
+                name
+            );
+            assert_eq!(services[0].description, "", "{}", name);
+            assert_eq!(services[0].url, None, "{}", name);
+        }
+
+        let services = parse(Path::new("list.yaml"), yaml).unwrap();
+        assert_eq!(services.len(), 2);
+        assert_eq!(
+            services[1].hashes().unwrap(),
+            vec![(Role::Contract, vec![0x52, 0x90])]
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(parse(Path::new("service.txt"), "id = 1").is_err());
+        assert!(parse(Path::new("service.yaml"), "").is_err());
+        // Float ID and unquoted short hex read as a number are rejected
+        assert!(parse(Path::new("service.yaml"), "id: 1.5\ntitle: A\nchains: [1]").is_err());
+        assert!(parse(
+            Path::new("service.yaml"),
+            "id: 1\ntitle: A\nchains: [1]\ncontracts: [0x1f]"
+        )
+        .is_err());
+        assert!(parse(Path::new("service.toml"), "id = 1\ntitle = \"A\"").is_err());
+        assert!(parse(
+            Path::new("service.json"),
+            r#"{"id": 1, "title": "A", "chains": [1], "tags": ["Nope"]}"#
+        )
+        .is_err());
+
+        let services = parse(
+            Path::new("service.json"),
+            r#"{"id": 1, "title": "A", "chains": [1], "pools": ["0xzz"]}"#,
+        )
+        .unwrap();
+        assert!(services[0].hashes().is_err());
+    }
+}
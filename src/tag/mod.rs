@@ -1,8 +1,10 @@
 use sea_orm::entity::prelude::*;
+use sea_orm::{ConnectionTrait, DbBackend, Statement};
 use sea_query::value::with_array::NotU8;
 use serde::{Deserialize, Serialize};
 
 // https://bitpay.com/blog/who-accepts-ethereum/
+// The tag table is seeded with these tags, parent and category follow the groups
 
 #[derive(Debug, Clone, Eq, PartialEq, EnumIter, DeriveActiveEnum, Deserialize, Serialize)]
 #[sea_orm(rs_type = "i32", db_type = "Integer")]
@@ -51,3 +53,30 @@ pub enum Tag {
 }
 
 impl NotU8 for Tag {}
+
+/// Tags together with all their descendants in the tag tree
+pub async fn descendants(db: &DatabaseConnection, tags: &[i32]) -> Result<Vec<i32>, String> {
+    if tags.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let statement = Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        r#"
+        WITH RECURSIVE tree AS (
+            SELECT unnest($1::integer[]) AS id
+            UNION
+            SELECT T.id FROM tag T JOIN tree ON T.parent = tree.id
+        )
+        SELECT id FROM tree
+        "#,
+        vec![tags.to_vec().into()],
+    );
+
+    db.query_all(statement)
+        .await
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|row| row.try_get("", "id").map_err(|e| e.to_string()))
+        .collect()
+}